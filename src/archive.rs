@@ -0,0 +1,537 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use tar::Archive;
+use xz2::read::XzDecoder;
+
+use crate::manifest::ExtraEntry;
+
+/// How a release asset packs the tool binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+    Zip,
+    /// The asset is the executable itself
+    Binary,
+}
+
+/// Guesses the format from the first bytes of the file, the extension of an
+/// asset is too often wrong or missing
+pub fn detect(path: &Path) -> Result<Option<ArchiveFormat>> {
+    let mut header = [0u8; 262];
+    let mut file = fs::File::open(path).context("opening downloaded asset")?;
+    let read = file.read(&mut header).context("reading downloaded asset")?;
+    let header = &header[..read];
+
+    let format = match header {
+        [0x1f, 0x8b, ..] => ArchiveFormat::TarGz,
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => ArchiveFormat::TarXz,
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => ArchiveFormat::TarZst,
+        [b'P', b'K', 0x03, 0x04, ..] => ArchiveFormat::Zip,
+        // ELF, Mach-O (32/64 bit, both endians, fat), PE and scripts
+        [0x7f, b'E', b'L', b'F', ..]
+        | [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..]
+        | [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..]
+        | [0xca, 0xfe, 0xba, 0xbe, ..]
+        | [b'M', b'Z', ..]
+        | [b'#', b'!', ..] => ArchiveFormat::Binary,
+        _ if header.get(257..262) == Some(b"ustar") => ArchiveFormat::Tar,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(format))
+}
+
+fn make_executable(binary_path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(binary_path)?.permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(binary_path, perms)?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = binary_path;
+
+    Ok(())
+}
+
+/// Where extra entries are staged, under the extraction dir
+pub const EXTRAS_DIR: &str = "extras";
+
+/// Decides where archive entries go: the main binary into the extraction
+/// dir, extras under `EXTRAS_DIR/<dest>`
+struct Selector<'a> {
+    install_dir: &'a Path,
+    binary_name: &'a str,
+    extras: Vec<(glob::Pattern, &'a ExtraEntry)>,
+    found_binary: bool,
+    /// Staged extras relative to `EXTRAS_DIR`, along with their archive path
+    staged_extras: Vec<(PathBuf, PathBuf)>,
+}
+
+impl<'a> Selector<'a> {
+    fn new(install_dir: &'a Path, binary_name: &'a str, extras: &'a [ExtraEntry]) -> Result<Self> {
+        let extras = extras
+            .iter()
+            .map(|extra| {
+                extra.validate()?;
+
+                let pattern = glob::Pattern::new(&extra.pattern)
+                    .with_context(|| format!("invalid extra pattern {}", extra.pattern))?;
+
+                Ok((pattern, extra))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            install_dir,
+            binary_name,
+            extras,
+            found_binary: false,
+            staged_extras: vec![],
+        })
+    }
+
+    /// Returns where to unpack an entry and whether it has to be executable,
+    /// or `None` to skip it. Fails if two different entries would land on
+    /// the same extra file.
+    fn select(&mut self, entry_path: &Path) -> Result<Option<(PathBuf, bool)>> {
+        let (Some(filename), Some(entry_path_str)) = (
+            entry_path.file_name().and_then(|name| name.to_str()),
+            entry_path.to_str(),
+        ) else {
+            return Ok(None);
+        };
+
+        if !self.found_binary && filename == self.binary_name {
+            self.found_binary = true;
+            return Ok(Some((self.install_dir.join(filename), true)));
+        }
+
+        // patterns without a dir match file names anywhere in the archive
+        let Some((_, extra)) = self.extras.iter().find(|(pattern, extra)| {
+            if extra.pattern.contains('/') {
+                pattern.matches(entry_path_str)
+            } else {
+                pattern.matches(filename)
+            }
+        }) else {
+            return Ok(None);
+        };
+
+        let relative = Path::new(&extra.dest).join(filename);
+
+        if let Some((_, staged_from)) = self.staged_extras.iter().find(|(r, _)| *r == relative) {
+            // tar archives may repeat an entry, the first one wins
+            if staged_from == entry_path {
+                return Ok(None);
+            }
+
+            anyhow::bail!(
+                "extras {} and {} would both be installed as {}",
+                staged_from.display(),
+                entry_path.display(),
+                relative.display()
+            );
+        }
+
+        self.staged_extras
+            .push((relative.clone(), entry_path.to_path_buf()));
+
+        let executable = extra.dest == "bin";
+
+        Ok(Some((
+            self.install_dir.join(EXTRAS_DIR).join(relative),
+            executable,
+        )))
+    }
+
+    fn finish(self) -> Result<Vec<PathBuf>> {
+        if !self.found_binary {
+            anyhow::bail!("No matching binary found in archive");
+        }
+
+        Ok(self
+            .staged_extras
+            .into_iter()
+            .map(|(relative, _)| relative)
+            .collect())
+    }
+}
+
+fn prepare_target(target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).context("creating extraction dir")?;
+    }
+
+    Ok(())
+}
+
+fn extract_tar_entries<R: Read>(
+    mut archive: Archive<R>,
+    mut selector: Selector,
+) -> Result<Vec<PathBuf>> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?.into_owned();
+
+        let Some((target, executable)) = selector.select(&path)? else {
+            continue;
+        };
+
+        prepare_target(&target)?;
+        entry.unpack(&target)?;
+
+        if executable {
+            make_executable(&target)?;
+        }
+    }
+
+    selector.finish()
+}
+
+fn extract_zip_entries(file: fs::File, mut selector: Selector) -> Result<Vec<PathBuf>> {
+    let mut archive = zip::ZipArchive::new(file).context("reading zip archive")?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+
+        if !entry.is_file() {
+            continue;
+        }
+
+        let Some(path) = entry.enclosed_name() else {
+            continue;
+        };
+
+        let Some((target, executable)) = selector.select(&path)? else {
+            continue;
+        };
+
+        prepare_target(&target)?;
+
+        let mut out = fs::File::create(&target)?;
+        std::io::copy(&mut entry, &mut out)?;
+
+        let executable_mode = entry.unix_mode().is_some_and(|mode| mode & 0o111 != 0);
+
+        if executable || executable_mode {
+            make_executable(&target)?;
+        }
+    }
+
+    selector.finish()
+}
+
+/// Extracts the binary named `binary_name` from the asset at `path` into
+/// `install_dir`, along with the entries matching `extras`. The format is
+/// detected unless given explicitly. Returns the staged extras, relative to
+/// `install_dir/EXTRAS_DIR`.
+pub fn extract_binary(
+    path: &Path,
+    format: Option<ArchiveFormat>,
+    install_dir: &Path,
+    binary_name: &str,
+    extras: &[ExtraEntry],
+) -> Result<Vec<PathBuf>> {
+    let format = match format {
+        Some(format) => format,
+        None => detect(path)?.with_context(|| {
+            format!(
+                "Unknown format of {}, declare it with `format` in the manifest",
+                path.display()
+            )
+        })?,
+    };
+
+    let file = fs::File::open(path)?;
+    let selector = Selector::new(install_dir, binary_name, extras)?;
+
+    let staged_extras = match format {
+        ArchiveFormat::Tar => extract_tar_entries(Archive::new(file), selector)?,
+        ArchiveFormat::TarGz => extract_tar_entries(Archive::new(GzDecoder::new(file)), selector)?,
+        ArchiveFormat::TarXz => extract_tar_entries(Archive::new(XzDecoder::new(file)), selector)?,
+        ArchiveFormat::TarZst => {
+            extract_tar_entries(Archive::new(zstd::Decoder::new(file)?), selector)?
+        }
+        ArchiveFormat::Zip => extract_zip_entries(file, selector)?,
+        ArchiveFormat::Binary => {
+            let binary_path = install_dir.join(binary_name);
+
+            // the asset is usually named after the release, not the tool
+            if path != binary_path {
+                fs::copy(path, &binary_path).context("copying binary asset")?;
+            }
+
+            make_executable(&binary_path)?;
+
+            // a bare executable has nothing else to offer
+            vec![]
+        }
+    };
+
+    Ok(staged_extras)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+
+    const ELF: &[u8] = b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+    fn tar_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+
+        for (path, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    fn tar_gz_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&tar_bytes(entries)).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zip_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+
+        for (path, content) in entries {
+            writer
+                .start_file(*path, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn write_asset(dir: &Path, name: &str, content: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn detects_formats_by_content() {
+        let tmp = tempfile::tempdir().unwrap();
+        let entries: &[(&str, &[u8])] = &[("trix", ELF)];
+
+        // extensions are deliberately misleading
+        for (name, content, expected) in [
+            ("a.zip", tar_bytes(entries), Some(ArchiveFormat::Tar)),
+            ("b.zip", tar_gz_bytes(entries), Some(ArchiveFormat::TarGz)),
+            ("c.tar.gz", zip_bytes(entries), Some(ArchiveFormat::Zip)),
+            ("d.tar.gz", ELF.to_vec(), Some(ArchiveFormat::Binary)),
+            (
+                "e",
+                b"#!/bin/sh\necho trix".to_vec(),
+                Some(ArchiveFormat::Binary),
+            ),
+            ("f.tar.gz", b"not found".to_vec(), None),
+            ("g", vec![], None),
+        ] {
+            let path = write_asset(tmp.path(), name, &content);
+            assert_eq!(detect(&path).unwrap(), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn extracts_the_binary_of_every_format() {
+        let tmp = tempfile::tempdir().unwrap();
+        let entries: &[(&str, &[u8])] = &[("README.md", b"readme"), ("dist/trix", ELF)];
+
+        for (name, content) in [
+            ("trix.tar", tar_bytes(entries)),
+            ("trix.tar.gz", tar_gz_bytes(entries)),
+            ("trix.zip", zip_bytes(entries)),
+            ("trix-x86_64-unknown-linux-gnu", ELF.to_vec()),
+        ] {
+            let asset = write_asset(tmp.path(), name, &content);
+            let install_dir = tmp.path().join(format!("{name}.out"));
+            fs::create_dir(&install_dir).unwrap();
+
+            let extras = extract_binary(&asset, None, &install_dir, "trix", &[]).unwrap();
+
+            assert!(extras.is_empty(), "{name}");
+            assert_eq!(fs::read(install_dir.join("trix")).unwrap(), ELF, "{name}");
+            assert!(!install_dir.join("README.md").exists(), "{name}");
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = fs::metadata(install_dir.join("trix"))
+                    .unwrap()
+                    .permissions()
+                    .mode();
+                assert_ne!(mode & 0o111, 0, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn fails_without_the_binary() {
+        let tmp = tempfile::tempdir().unwrap();
+        let asset = write_asset(
+            tmp.path(),
+            "trix.tar.gz",
+            &tar_gz_bytes(&[("tx3-lsp", ELF)]),
+        );
+
+        let err = extract_binary(&asset, None, tmp.path(), "trix", &[]).unwrap_err();
+        assert!(err.to_string().contains("No matching binary"), "{err}");
+    }
+
+    fn extra(pattern: &str, dest: &str) -> ExtraEntry {
+        ExtraEntry {
+            pattern: pattern.to_string(),
+            dest: dest.to_string(),
+        }
+    }
+
+    #[test]
+    fn stages_extras_under_their_destination() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let asset = write_asset(
+            tmp.path(),
+            "trix.zip",
+            &zip_bytes(&[
+                ("trix-0.5.0/trix", ELF),
+                ("trix-0.5.0/trix-lsp", ELF),
+                ("trix-0.5.0/doc/trix.1", b"man"),
+                ("trix-0.5.0/completions/trix.bash", b"complete"),
+                ("trix-0.5.0/completions/trix.fish", b"complete"),
+            ]),
+        );
+
+        let extras = [
+            extra("trix-lsp", "bin"),
+            extra("*.1", "share/man/man1"),
+            extra("*/completions/*.bash", "share/completions"),
+        ];
+
+        let mut staged = extract_binary(&asset, None, tmp.path(), "trix", &extras).unwrap();
+        staged.sort();
+
+        assert_eq!(
+            staged,
+            vec![
+                PathBuf::from("bin/trix-lsp"),
+                PathBuf::from("share/completions/trix.bash"),
+                PathBuf::from("share/man/man1/trix.1"),
+            ]
+        );
+
+        let extras_dir = tmp.path().join(EXTRAS_DIR);
+        assert_eq!(
+            fs::read(extras_dir.join("share/man/man1/trix.1")).unwrap(),
+            b"man"
+        );
+        assert!(!extras_dir.join("share/completions/trix.fish").exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(extras_dir.join("bin/trix-lsp"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_ne!(mode & 0o111, 0);
+        }
+    }
+
+    #[test]
+    fn repeated_entries_keep_the_first_copy() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        // tar archives may hold the same path twice
+        let asset = write_asset(
+            tmp.path(),
+            "trix.tar.gz",
+            &tar_gz_bytes(&[
+                ("trix", ELF),
+                ("doc/README", b"first"),
+                ("doc/README", b"second"),
+            ]),
+        );
+
+        let staged = extract_binary(
+            &asset,
+            None,
+            tmp.path(),
+            "trix",
+            &[extra("README", "share/doc")],
+        )
+        .unwrap();
+
+        assert_eq!(staged, vec![PathBuf::from("share/doc/README")]);
+        assert_eq!(
+            fs::read(tmp.path().join(EXTRAS_DIR).join("share/doc/README")).unwrap(),
+            b"first"
+        );
+    }
+
+    #[test]
+    fn colliding_extras_fail() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let asset = write_asset(
+            tmp.path(),
+            "trix.tar.gz",
+            &tar_gz_bytes(&[("trix", ELF), ("a/README", b"a"), ("b/README", b"b")]),
+        );
+
+        let err = extract_binary(
+            &asset,
+            None,
+            tmp.path(),
+            "trix",
+            &[extra("README", "share/doc")],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "extras a/README and b/README would both be installed as share/doc/README"
+        );
+    }
+
+    #[test]
+    fn extras_cant_escape_the_channel() {
+        let tmp = tempfile::tempdir().unwrap();
+        let asset = write_asset(tmp.path(), "trix.tar.gz", &tar_gz_bytes(&[("trix", ELF)]));
+
+        for dest in ["../bin", "/usr/bin"] {
+            let result = extract_binary(&asset, None, tmp.path(), "trix", &[extra("*", dest)]);
+            assert!(result.is_err(), "{dest}");
+        }
+    }
+}
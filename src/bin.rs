@@ -1,33 +1,279 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::LazyLock,
+    time::Duration,
+};
+
 use tokio::process::Command;
 
 use anyhow::Context;
+use regex::Regex;
 use semver::Version;
 
 use crate::{Config, manifest::Tool};
 
-pub async fn run_version_cmd(tool: &Tool, config: &Config) -> anyhow::Result<String> {
-    let version = tool.version_cmd();
+static SELF_EXE: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    std::env::current_exe()
+        .and_then(|exe| exe.canonicalize())
+        .ok()
+});
+
+/// Path of the `tx3up` executable. It's resolved once, since after a self
+/// update the OS reports the replaced file as deleted.
+pub fn self_exe() -> anyhow::Result<&'static Path> {
+    SELF_EXE.as_deref().context("locating tx3up executable")
+}
+
+/// Name of the executable file of a tool on this platform
+pub fn file_name(tool_name: &str) -> String {
+    format!("{tool_name}{}", std::env::consts::EXE_SUFFIX)
+}
+
+/// Whether a file can be run, going by its permissions on unix
+pub fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+
+    #[cfg(not(unix))]
+    metadata.is_file()
+}
+
+/// Every executable named after a tool on `PATH`, in lookup order
+pub fn find_all_on_path(tool_name: &str) -> Vec<PathBuf> {
+    let Some(path) = std::env::var_os("PATH") else {
+        return vec![];
+    };
+
+    let file_name = file_name(tool_name);
+    let mut found = vec![];
+
+    for candidate in std::env::split_paths(&path).map(|dir| dir.join(&file_name)) {
+        if is_executable(&candidate) && !found.contains(&candidate) {
+            found.push(candidate);
+        }
+    }
 
-    let bin_path = tool.bin_path(config);
+    found
+}
+
+/// Whether two paths name the same dir once symlinks are resolved
+pub fn same_dir(a: &Path, b: &Path) -> bool {
+    let canonical = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+
+    canonical(a) == canonical(b)
+}
 
-    let output = Command::new(bin_path)
-        .arg(version)
-        .output()
+/// The copy of a tool that `PATH` resolves to, when it isn't one managed by
+/// tx3up: neither a shim nor a channel binary put on PATH by older versions
+pub fn find_shadowing(tool_name: &str, config: &Config) -> Option<PathBuf> {
+    let first = find_all_on_path(tool_name).into_iter().next()?;
+    let dir = first.parent()?;
+
+    let managed = [
+        config.shims_dir(),
+        config.fixed_channel_dir().join("bin"),
+        config.bin_dir(),
+    ];
+
+    if managed.iter().any(|m| same_dir(m, dir)) {
+        return None;
+    }
+
+    Some(first)
+}
+
+/// How long a version command may run before the binary is deemed broken
+const VERSION_CMD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Matches the first semver-looking token, e.g. in `trix 0.5.0 (abc123)`
+static DEFAULT_VERSION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"v?(\d+\.\d+\.\d+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?)").unwrap()
+});
+
+/// Runs the version command of a tool, returning its stdout and stderr
+pub async fn run_version_cmd(tool: &Tool, bin_path: &Path) -> anyhow::Result<(String, String)> {
+    let mut cmd = Command::new(bin_path);
+
+    cmd.args(tool.version_args())
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(VERSION_CMD_TIMEOUT, cmd.output())
         .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "version command timed out after {}s",
+                VERSION_CMD_TIMEOUT.as_secs()
+            )
+        })?
         .context("running version command")?;
 
-    String::from_utf8(output.stdout).context("parsing version output")
+    Ok((
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    ))
 }
 
-pub async fn check_current_version(tool: &Tool, config: &Config) -> anyhow::Result<Version> {
-    let raw_version = run_version_cmd(tool, config).await?;
+fn extract_from_json(output: &str, path: &str) -> anyhow::Result<String> {
+    let json: serde_json::Value = serde_json::from_str(output).context("parsing version json")?;
 
-    let raw_version = raw_version
-        .split_whitespace()
-        .last()
+    let value = path
+        .split('.')
+        .try_fold(&json, |value, key| value.get(key))
+        .with_context(|| format!("no {path} in version json"))?;
+
+    value
+        .as_str()
+        .map(str::to_string)
+        .with_context(|| format!("{path} in version json is not a string"))
+}
+
+fn extract_with_regex(output: &str, regex: &Regex) -> anyhow::Result<String> {
+    let captures = regex
+        .captures(output)
         .context("no version found in output")?;
 
-    let version = Version::parse(raw_version).context("parsing version")?;
+    let found = captures.get(1).or_else(|| captures.get(0)).unwrap();
+
+    Ok(found.as_str().to_string())
+}
+
+fn extract_version(tool: &Tool, output: &str) -> anyhow::Result<Version> {
+    let raw_version = if let Some(path) = &tool.version_json_path {
+        extract_from_json(output, path)?
+    } else if let Some(regex) = &tool.version_regex {
+        extract_with_regex(output, regex.regex())?
+    } else {
+        extract_with_regex(output, &DEFAULT_VERSION_REGEX)?
+    };
+
+    let raw_version = raw_version.trim().trim_start_matches('v');
+
+    Version::parse(raw_version).context("parsing version")
+}
+
+/// Looks for the version in stdout, then in stderr since some tools print
+/// it there
+fn extract_output_version(tool: &Tool, stdout: &str, stderr: &str) -> anyhow::Result<Version> {
+    extract_version(tool, stdout).or_else(|err| extract_version(tool, stderr).map_err(|_| err))
+}
+
+pub async fn check_version(tool: &Tool, bin_path: &Path) -> anyhow::Result<Version> {
+    let (stdout, stderr) = run_version_cmd(tool, bin_path).await?;
+
+    extract_output_version(tool, &stdout, &stderr)
+}
+
+pub async fn check_current_version(tool: &Tool, config: &Config) -> anyhow::Result<Version> {
+    check_version(tool, &tool.bin_path(config)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(extra: serde_json::Value) -> Tool {
+        let mut json = serde_json::json!({
+            "name": "trix",
+            "description": "",
+            "repo_owner": "tx3-lang",
+            "repo_name": "trix",
+            "version": "^0.5",
+        });
+
+        json.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn default_regex_takes_the_first_semver_token() {
+        let tool = tool(serde_json::json!({}));
+
+        for (output, expected) in [
+            ("trix 0.5.0 (abc123 2025-01-01)", "0.5.0"),
+            ("trix v0.5.0\n", "0.5.0"),
+            ("tx3-lsp 1.2.3-beta.1+build.5", "1.2.3-beta.1+build.5"),
+            ("version 2.0.0, built with rustc 1.80.0", "2.0.0"),
+        ] {
+            let version = extract_version(&tool, output).unwrap();
+            assert_eq!(version, Version::parse(expected).unwrap(), "{output}");
+        }
+
+        assert!(extract_version(&tool, "trix dev build").is_err());
+    }
+
+    #[test]
+    fn custom_regex_uses_the_first_capture_group() {
+        let tool = tool(serde_json::json!({
+            "version_regex": r"release: (\S+)",
+        }));
+
+        let version = extract_version(&tool, "commit 1.0.0-abc\nrelease: v0.7.1").unwrap();
+        assert_eq!(version, Version::parse("0.7.1").unwrap());
+
+        let regex = Regex::new(r"\d+\.\d+\.\d+").unwrap();
+        assert_eq!(extract_with_regex("at 3.2.1", &regex).unwrap(), "3.2.1");
+        assert!(extract_with_regex("none here", &regex).is_err());
+    }
+
+    #[test]
+    fn invalid_regex_fails_the_manifest() {
+        let json = serde_json::json!({
+            "name": "trix",
+            "description": "",
+            "repo_owner": "tx3-lang",
+            "repo_name": "trix",
+            "version": "^0.5",
+            "version_regex": "(unclosed",
+        });
+
+        let err = serde_json::from_value::<Tool>(json).unwrap_err();
+        assert!(err.to_string().contains("invalid version_regex"), "{err}");
+    }
+
+    #[test]
+    fn json_path_reads_nested_strings() {
+        let output = r#"{"package": {"name": "trix", "version": "0.5.0", "major": 0}}"#;
+
+        assert_eq!(
+            extract_from_json(output, "package.version").unwrap(),
+            "0.5.0"
+        );
+        assert!(extract_from_json(output, "package.missing").is_err());
+        assert!(extract_from_json(output, "package.major").is_err());
+        assert!(extract_from_json("trix 0.5.0", "package.version").is_err());
+
+        let tool = tool(serde_json::json!({
+            "version_json_path": "package.version",
+        }));
+
+        let version = extract_version(&tool, output).unwrap();
+        assert_eq!(version, Version::parse("0.5.0").unwrap());
+    }
+
+    #[test]
+    fn falls_back_to_stderr() {
+        let tool = tool(serde_json::json!({}));
+
+        let version = extract_output_version(&tool, "", "trix 0.5.0\n").unwrap();
+        assert_eq!(version, Version::parse("0.5.0").unwrap());
+
+        // stdout wins when both have one
+        let version = extract_output_version(&tool, "trix 0.6.0", "trix 0.5.0").unwrap();
+        assert_eq!(version, Version::parse("0.6.0").unwrap());
 
-    Ok(version)
+        let err = extract_output_version(&tool, "usage: trix", "unknown flag").unwrap_err();
+        assert!(err.to_string().contains("no version found"), "{err}");
+    }
 }
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Config,
+    checksum::{self, ExpectedDigest},
+};
+
+/// Held while the cache is changed or a blob is copied out of it. The cache
+/// is shared by every tx3up process, on top of the tools staged in parallel
+/// by one, so it's a file lock.
+const LOCK_FILE: &str = "index.lock";
+
+const INDEX_FILE: &str = "index.json";
+
+/// A release asset kept in the download cache, stored under its SHA-256
+/// digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAsset {
+    pub tool: String,
+    pub version: String,
+    pub target: String,
+    pub name: String,
+    pub url: String,
+    pub sha256: String,
+
+    /// Unix timestamp of the last time the asset was stored or reused
+    #[serde(default)]
+    pub last_used: u64,
+}
+
+impl CachedAsset {
+    fn of_file(
+        tool: &str,
+        version: &Version,
+        target: &str,
+        name: &str,
+        url: &str,
+        file: &Path,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            tool: tool.to_string(),
+            version: version.to_string(),
+            target: target.to_string(),
+            name: name.to_string(),
+            url: url.to_string(),
+            sha256: checksum::file_digest(file)?,
+            last_used: now(),
+        })
+    }
+
+    pub fn version(&self) -> Option<Version> {
+        Version::parse(&self.version).ok()
+    }
+
+    fn matches_digests(&self, expected: &[ExpectedDigest]) -> bool {
+        expected
+            .iter()
+            .all(|digest| digest.hex.trim().eq_ignore_ascii_case(&self.sha256))
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn blob_path(sha256: &str, config: &Config) -> PathBuf {
+    config.download_cache_dir().join(sha256)
+}
+
+/// Size of a cached blob, zero if it's gone
+pub fn blob_size(sha256: &str, config: &Config) -> u64 {
+    std::fs::metadata(blob_path(sha256, config))
+        .map(|m| m.len())
+        .unwrap_or_default()
+}
+
+fn index_file(config: &Config) -> PathBuf {
+    config.download_cache_dir().join(INDEX_FILE)
+}
+
+/// Takes the cache lock, which is released when the returned file is dropped
+fn lock(config: &Config) -> anyhow::Result<File> {
+    std::fs::create_dir_all(config.download_cache_dir()).context("creating download cache")?;
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(config.download_cache_dir().join(LOCK_FILE))
+        .context("opening download cache lock")?;
+
+    file.lock().context("locking download cache")?;
+
+    Ok(file)
+}
+
+/// Files of the cache dir that are neither blobs nor stray files
+fn is_bookkeeping(name: &str) -> bool {
+    // partial blobs are being copied in by another process
+    name == INDEX_FILE || name == LOCK_FILE || name.ends_with(".partial")
+}
+
+pub fn load_index(config: &Config) -> anyhow::Result<Vec<CachedAsset>> {
+    let index_file = index_file(config);
+
+    if !index_file.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = std::fs::read_to_string(index_file).context("reading download cache index")?;
+
+    serde_json::from_str(&content).context("parsing download cache index")
+}
+
+fn save_index(index: &[CachedAsset], config: &Config) -> anyhow::Result<()> {
+    std::fs::create_dir_all(config.download_cache_dir()).context("creating download cache")?;
+
+    // readers don't take the lock, so they must never see half an index
+    let partial = index_file(config).with_extension("partial");
+
+    std::fs::write(&partial, serde_json::to_string_pretty(index)?)
+        .context("writing download cache index")?;
+    std::fs::rename(&partial, index_file(config)).context("writing download cache index")?;
+
+    Ok(())
+}
+
+/// Keeps a copy of a downloaded (and verified) asset around for later
+/// installs, evicting the least recently used assets past the size limit.
+pub fn store(
+    tool: &str,
+    version: &Version,
+    target: &str,
+    name: &str,
+    url: &str,
+    file: &Path,
+    config: &Config,
+) -> anyhow::Result<CachedAsset> {
+    let entry = CachedAsset::of_file(tool, version, target, name, url, file)?;
+
+    add(entry, file, config.cache_size_limit(), config)
+}
+
+/// Like [`store`], but never evicts anything: the assets of a bundle must
+/// all be there for the install that follows, even past the size limit.
+pub fn import(
+    tool: &str,
+    version: &Version,
+    target: &str,
+    name: &str,
+    url: &str,
+    file: &Path,
+    config: &Config,
+) -> anyhow::Result<CachedAsset> {
+    let entry = CachedAsset::of_file(tool, version, target, name, url, file)?;
+
+    add(entry, file, None, config)
+}
+
+fn add(
+    entry: CachedAsset,
+    file: &Path,
+    limit: Option<u64>,
+    config: &Config,
+) -> anyhow::Result<CachedAsset> {
+    let blob = blob_path(&entry.sha256, config);
+
+    let _lock = lock(config)?;
+
+    if !blob.exists() {
+        // copy under a temp name first so that a partial blob is never
+        // mistaken for a complete one
+        let partial = blob.with_extension("partial");
+        std::fs::copy(file, &partial).context("copying asset into download cache")?;
+        std::fs::rename(&partial, &blob).context("moving asset into download cache")?;
+    }
+
+    let mut index = load_index(config)?;
+    index.retain(|cached| cached.url != entry.url);
+    index.push(entry.clone());
+
+    // never evict what was just stored
+    evict(&mut index, limit, Some(&entry.url), config)?;
+    save_index(&index, config)?;
+
+    Ok(entry)
+}
+
+/// Looks up a previous download of `url` whose content matches every
+/// expected digest.
+pub fn find_by_url(
+    url: &str,
+    expected: &[ExpectedDigest],
+    config: &Config,
+) -> anyhow::Result<Option<CachedAsset>> {
+    let index = load_index(config)?;
+
+    let found = index
+        .into_iter()
+        .filter(|cached| cached.url == url && cached.matches_digests(expected))
+        .find(|cached| blob_path(&cached.sha256, config).exists());
+
+    Ok(found)
+}
+
+/// Finds the highest cached version of a tool matching `requested` for the
+/// given target.
+pub fn find_asset(
+    tool: &str,
+    requested: &VersionReq,
+    target: &str,
+    config: &Config,
+) -> anyhow::Result<Option<CachedAsset>> {
+    let index = load_index(config)?;
+
+    let best = index
+        .into_iter()
+        .filter(|cached| cached.tool == tool && cached.target == target)
+        .filter(|cached| blob_path(&cached.sha256, config).exists())
+        .filter_map(|cached| Some((cached.version()?, cached)))
+        .filter(|(version, _)| requested.matches(version))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, cached)| cached);
+
+    Ok(best)
+}
+
+/// Copies the blob of a cached asset to `dest`, making sure no other process
+/// evicts it halfway through, and bumps its last use.
+pub fn copy_out(cached: &CachedAsset, dest: &Path, config: &Config) -> anyhow::Result<()> {
+    let _lock = lock(config)?;
+
+    let blob = blob_path(&cached.sha256, config);
+
+    if !blob.exists() {
+        anyhow::bail!("{} was evicted from the download cache", cached.name);
+    }
+
+    std::fs::copy(blob, dest).context("copying asset from download cache")?;
+
+    // bumping the last use makes it evicted later
+    let mut index = load_index(config)?;
+
+    for entry in index.iter_mut().filter(|entry| entry.url == cached.url) {
+        entry.last_used = now();
+    }
+
+    save_index(&index, config)
+}
+
+/// Total size of the distinct blobs referenced by the index
+pub fn total_size(index: &[CachedAsset], config: &Config) -> u64 {
+    let blobs: HashSet<_> = index.iter().map(|cached| cached.sha256.as_str()).collect();
+
+    blobs.iter().map(|sha256| blob_size(sha256, config)).sum()
+}
+
+/// Drops the least recently used entries until the cache fits in `limit`
+/// bytes, deleting the blobs nothing refers to anymore. Returns the evicted
+/// entries.
+fn evict(
+    index: &mut Vec<CachedAsset>,
+    limit: Option<u64>,
+    keep: Option<&str>,
+    config: &Config,
+) -> anyhow::Result<Vec<CachedAsset>> {
+    let Some(limit) = limit else {
+        return Ok(vec![]);
+    };
+
+    index.sort_by_key(|cached| cached.last_used);
+
+    let mut evicted = vec![];
+    let mut size = total_size(index, config);
+    let mut i = 0;
+
+    while size > limit && i < index.len() {
+        if Some(index[i].url.as_str()) == keep {
+            i += 1;
+            continue;
+        }
+
+        let cached = index.remove(i);
+
+        // blobs are shared by every url serving the same content
+        if !index.iter().any(|other| other.sha256 == cached.sha256) {
+            size -= blob_size(&cached.sha256, config);
+
+            std::fs::remove_file(blob_path(&cached.sha256, config))
+                .context("removing cached asset")?;
+        }
+
+        evicted.push(cached);
+    }
+
+    Ok(evicted)
+}
+
+/// Drops index entries whose blob is gone, deletes files nothing refers to
+/// and enforces the size limit. Returns the evicted entries.
+pub fn prune(config: &Config) -> anyhow::Result<Vec<CachedAsset>> {
+    let cache_dir = config.download_cache_dir();
+
+    if !cache_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let _lock = lock(config)?;
+
+    let (mut kept, mut evicted): (Vec<_>, Vec<_>) = load_index(config)?
+        .into_iter()
+        .partition(|cached| blob_path(&cached.sha256, config).exists());
+
+    evicted.extend(evict(&mut kept, config.cache_size_limit(), None, config)?);
+
+    let referenced: HashSet<_> = kept.iter().map(|cached| cached.sha256.as_str()).collect();
+
+    for entry in std::fs::read_dir(&cache_dir).context("reading download cache")? {
+        let path = entry?.path();
+
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+
+        if is_bookkeeping(name) || referenced.contains(name) {
+            continue;
+        }
+
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path).context("removing stray cache dir")?;
+        } else {
+            std::fs::remove_file(&path).context("removing stray cache file")?;
+        }
+    }
+
+    save_index(&kept, config)?;
+
+    Ok(evicted)
+}
+
+/// Removes every cached asset, along with unfinished downloads
+pub fn clear(config: &Config) -> anyhow::Result<()> {
+    let cache_dir = config.download_cache_dir();
+
+    if cache_dir.exists() {
+        let _lock = lock(config)?;
+
+        // the lock file stays, other processes may be waiting on it
+        for entry in std::fs::read_dir(&cache_dir).context("reading download cache")? {
+            let path = entry?.path();
+
+            if path.file_name().is_some_and(|name| name == LOCK_FILE) {
+                continue;
+            }
+
+            if path.is_dir() {
+                std::fs::remove_dir_all(&path).context("removing download cache")?;
+            } else {
+                std::fs::remove_file(&path).context("removing download cache")?;
+            }
+        }
+    }
+
+    let partial_dir = config.partial_downloads_dir();
+
+    if partial_dir.exists() {
+        std::fs::remove_dir_all(partial_dir).context("removing partial downloads")?;
+    }
+
+    Ok(())
+}
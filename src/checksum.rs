@@ -0,0 +1,166 @@
+use std::path::Path;
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    Config,
+    download::{self, DownloadOptions},
+    sources::{Asset, Release},
+};
+
+/// Name of the release-wide checksum asset listing every asset of a release
+const RELEASE_SUMS_ASSET: &str = "SHA256SUMS";
+
+#[derive(Debug, Clone)]
+pub struct ExpectedDigest {
+    /// Where the digest came from, used for error reporting
+    pub source: String,
+    pub hex: String,
+}
+
+pub fn file_digest(path: &Path) -> anyhow::Result<String> {
+    let mut file = std::fs::File::open(path).context("opening file to hash")?;
+    let mut hasher = Sha256::new();
+
+    std::io::copy(&mut file, &mut hasher).context("hashing file")?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn normalize(hex: &str) -> String {
+    hex.trim().to_lowercase()
+}
+
+/// Parses the output format of `sha256sum`, returning the digest for
+/// `asset_name`. A file with a single bare digest is accepted as well, since
+/// that's what per-asset `.sha256` files usually contain.
+fn parse_checksum_file(content: &str, asset_name: &str) -> Option<String> {
+    let lines: Vec<_> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    for line in lines.iter() {
+        let mut parts = line.split_whitespace();
+
+        let Some(digest) = parts.next() else {
+            continue;
+        };
+
+        match parts.next() {
+            Some(name) => {
+                let name = name.trim_start_matches('*');
+                let name = name.rsplit('/').next().unwrap_or(name);
+
+                if name == asset_name {
+                    return Some(normalize(digest));
+                }
+            }
+            None if lines.len() == 1 => return Some(normalize(digest)),
+            None => continue,
+        }
+    }
+
+    None
+}
+
+async fn fetch_checksum_content(url: &str, config: &Config) -> anyhow::Result<String> {
+    let options = DownloadOptions::from_config(config);
+
+    download::fetch_text(url, &options)
+        .await
+        .context("fetching checksum")
+}
+
+/// Looks for a checksum published next to `asset`, either as a dedicated
+/// `<asset>.sha256` file or as an entry of a release-wide `SHA256SUMS` file.
+pub async fn find_release_digest(
+    release: &Release,
+    asset: &Asset,
+    config: &Config,
+) -> anyhow::Result<Option<ExpectedDigest>> {
+    let sibling_name = format!("{}.sha256", asset.name);
+
+    let candidates = [sibling_name.as_str(), RELEASE_SUMS_ASSET];
+
+    for candidate in candidates {
+        let Some(checksum_asset) = release.asset_by_name(candidate) else {
+            continue;
+        };
+
+        let content = fetch_checksum_content(&checksum_asset.url, config).await?;
+
+        if let Some(hex) = parse_checksum_file(&content, &asset.name) {
+            return Ok(Some(ExpectedDigest {
+                source: checksum_asset.name.clone(),
+                hex,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Checks the file at `path` against every expected digest, failing on the
+/// first mismatch.
+pub fn verify_file(path: &Path, expected: &[ExpectedDigest]) -> anyhow::Result<()> {
+    let actual = file_digest(path)?;
+
+    for digest in expected {
+        if normalize(&digest.hex) != actual {
+            anyhow::bail!(
+                "checksum mismatch for {} (from {}): expected {}, got {}",
+                path.display(),
+                digest.source,
+                digest.hex,
+                actual
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASSET: &str = "trix-x86_64-unknown-linux-gnu.tar.gz";
+    const DIGEST: &str = "5199a17169f113d8e722a3db48c5fa50849475cd29f16f4cdbff4cd2ef35ba29";
+    const OTHER: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+    #[test]
+    fn finds_the_digest_of_the_asset() {
+        for (content, expected) in [
+            // sha256sum in text mode
+            (format!("{DIGEST}  {ASSET}\n"), Some(DIGEST)),
+            // sha256sum in binary mode
+            (format!("{DIGEST} *{ASSET}\n"), Some(DIGEST)),
+            // names with the dir they were hashed from
+            (format!("{DIGEST}  dist/{ASSET}\n"), Some(DIGEST)),
+            (format!("{DIGEST} *./dist/{ASSET}\n"), Some(DIGEST)),
+            // a dedicated `.sha256` file with just the digest
+            (format!("{DIGEST}\n"), Some(DIGEST)),
+            (format!("  {}  \n\n", DIGEST.to_uppercase()), Some(DIGEST)),
+            // a release-wide file
+            (
+                format!("{OTHER}  trix-aarch64-apple-darwin.tar.gz\n{DIGEST}  {ASSET}\n"),
+                Some(DIGEST),
+            ),
+            // a release-wide file without the asset
+            (
+                format!("{OTHER}  trix-aarch64-apple-darwin.tar.gz\n{OTHER}  trix.zip\n"),
+                None,
+            ),
+            // bare digests only count when they're alone
+            (format!("{OTHER}\n{DIGEST}\n"), None),
+            // a longer name that ends like the asset
+            (format!("{OTHER}  x{ASSET}\n"), None),
+            (String::new(), None),
+        ] {
+            assert_eq!(
+                parse_checksum_file(&content, ASSET).as_deref(),
+                expected,
+                "{content:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use indicatif::MultiProgress;
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ArgsCommon, Config,
+    cache::{self, CachedAsset},
+    checksum::{self, ExpectedDigest},
+    cmds::install,
+    manifest::{self, Installer, Manifest, ManifestSource},
+    overrides, signature,
+};
+
+const BUNDLE_INDEX: &str = "bundle.json";
+const MANIFEST_ENTRY: &str = "manifest.json";
+const SIGNATURE_ENTRY: &str = "manifest.json.minisig";
+const ASSETS_DIR: &str = "assets";
+
+#[derive(Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Package the channel manifest and the release archives of its tools
+    Create {
+        /// File to write the bundle to
+        #[arg(short, long, default_value = "toolchain.tar")]
+        output: PathBuf,
+
+        /// Target triples to include, comma-separated (default: the current
+        /// one)
+        #[arg(long, value_delimiter = ',')]
+        targets: Vec<String>,
+    },
+    /// Install the toolchain from a bundle, without touching the network
+    Install {
+        /// Bundle created with `tx3up bundle create`
+        bundle: PathBuf,
+    },
+}
+
+impl ArgsCommon for Args {
+    fn skip_banner(&self) -> bool {
+        // the banner would create the default channel before the bundle gets
+        // to pick it
+        matches!(self.command, Command::Install { .. })
+    }
+}
+
+/// Describes the content of a bundle
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleIndex {
+    channel: String,
+
+    /// Tag of the toolchain release the manifest was taken from, if tagged
+    release: Option<String>,
+
+    source: ManifestSource,
+
+    /// Version requirements that stood in for the manifest's when the bundle
+    /// was made, from overrides and project pins. The archives are picked by
+    /// them, so they become the channel's overrides on import.
+    #[serde(default)]
+    overrides: HashMap<String, String>,
+
+    /// Release archives, stored as `assets/<sha256>`
+    assets: Vec<CachedAsset>,
+}
+
+/// Scratch dir for building or unpacking a bundle, emptied beforehand
+fn work_dir(config: &Config) -> anyhow::Result<PathBuf> {
+    let dir = config.bundle_work_dir();
+
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).context("cleaning up bundle work dir")?;
+    }
+
+    std::fs::create_dir_all(dir.join(ASSETS_DIR)).context("creating bundle work dir")?;
+
+    Ok(dir)
+}
+
+fn write_bundle(output: &Path, index: &BundleIndex, work_dir: &Path) -> anyhow::Result<()> {
+    let file =
+        std::fs::File::create(output).with_context(|| format!("creating {}", output.display()))?;
+
+    let mut builder = tar::Builder::new(file);
+
+    let index = serde_json::to_vec_pretty(index)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(index.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, BUNDLE_INDEX, index.as_slice())?;
+
+    builder.append_path_with_name(work_dir.join(MANIFEST_ENTRY), MANIFEST_ENTRY)?;
+
+    if work_dir.join(SIGNATURE_ENTRY).exists() {
+        builder.append_path_with_name(work_dir.join(SIGNATURE_ENTRY), SIGNATURE_ENTRY)?;
+    }
+
+    builder.append_dir_all(ASSETS_DIR, work_dir.join(ASSETS_DIR))?;
+
+    builder.into_inner()?.sync_all()?;
+
+    Ok(())
+}
+
+async fn create(output: &Path, targets: &[String], config: &Config) -> anyhow::Result<()> {
+    let targets = match targets {
+        [] => vec![config.target()?],
+        targets => targets.to_vec(),
+    };
+
+    let manifest = manifest::load_latest_manifest(config, true).await?;
+
+    let work_dir = work_dir(config)?;
+
+    // the signed manifest goes in as downloaded, so it can be verified again
+    // on the other end
+    std::fs::copy(config.manifest_file(), work_dir.join(MANIFEST_ENTRY))
+        .context("copying manifest")?;
+
+    if config.manifest_signature_file().exists() {
+        std::fs::copy(
+            config.manifest_signature_file(),
+            work_dir.join(SIGNATURE_ENTRY),
+        )
+        .context("copying manifest signature")?;
+    }
+
+    // the bundled manifest is the raw one, whatever overrides it here has to
+    // travel along
+    let raw: Manifest = serde_json::from_str(
+        &std::fs::read_to_string(config.manifest_file()).context("reading manifest file")?,
+    )
+    .context("parsing manifest file")?;
+
+    let overrides: HashMap<_, _> = manifest
+        .tools()
+        .filter(|tool| {
+            raw.tool_by_name(&tool.name)
+                .is_some_and(|raw_tool| raw_tool.version != tool.version)
+        })
+        .map(|tool| (tool.name.clone(), tool.version.clone()))
+        .collect();
+
+    let multi = MultiProgress::new();
+    let mut assets = vec![];
+
+    for tool in manifest.tools() {
+        if !matches!(tool.installer, Installer::GithubRelease) {
+            continue;
+        }
+
+        let requested = VersionReq::parse(&tool.version)?;
+        let progress = install::tool_progress(&multi, tool);
+
+        for target in targets.iter() {
+            progress.set_message(format!("looking for a release for {target}"));
+
+            let picked = install::resolve_asset(tool, &requested, target, config).await?;
+            let archive = install::fetch_asset(tool, &picked, &work_dir, config, &progress).await?;
+
+            let sha256 = checksum::file_digest(&archive)?;
+            std::fs::rename(&archive, work_dir.join(ASSETS_DIR).join(&sha256))
+                .context("moving archive into bundle")?;
+
+            assets.push(CachedAsset {
+                tool: tool.name.clone(),
+                version: picked.version.to_string(),
+                target: target.clone(),
+                name: picked.asset.name.clone(),
+                url: picked.asset.url.clone(),
+                sha256,
+                last_used: 0,
+            });
+        }
+
+        progress.finish_with_message("bundled");
+    }
+
+    let index = BundleIndex {
+        channel: config.ensure_channel(),
+        release: std::fs::read_to_string(config.manifest_release_file()).ok(),
+        source: manifest::manifest_source(config).await?,
+        overrides,
+        assets,
+    };
+
+    write_bundle(output, &index, &work_dir)?;
+
+    std::fs::remove_dir_all(&work_dir).context("cleaning up bundle work dir")?;
+
+    println!(
+        "\nBundled {} archives of channel {} for {} into {} 📦",
+        index.assets.len(),
+        index.channel,
+        targets.join(", "),
+        output.display()
+    );
+
+    Ok(())
+}
+
+/// Puts the bundled manifest in place as the cached manifest of the channel
+fn import_manifest(index: &BundleIndex, work_dir: &Path, config: &Config) -> anyhow::Result<()> {
+    let manifest_content = std::fs::read_to_string(work_dir.join(MANIFEST_ENTRY))
+        .context("reading bundled manifest")?;
+    let signature_path = work_dir.join(SIGNATURE_ENTRY);
+
+    let signature_content = if signature_path.exists() {
+        Some(
+            std::fs::read_to_string(signature_path)
+                .context("reading bundled manifest signature")?,
+        )
+    } else {
+        None
+    };
+
+    signature::verify_manifest(
+        manifest_content.as_bytes(),
+        signature_content.as_deref(),
+        manifest::previously_signed(config)?,
+        config,
+    )
+    .context("verifying bundled manifest signature")?;
+
+    std::fs::create_dir_all(config.channel_dir()).context("creating channel dir")?;
+
+    std::fs::write(config.manifest_file(), manifest_content).context("writing manifest file")?;
+
+    match &signature_content {
+        Some(signature) => std::fs::write(config.manifest_signature_file(), signature)
+            .context("writing manifest signature file")?,
+        None if config.manifest_signature_file().exists() => {
+            std::fs::remove_file(config.manifest_signature_file())
+                .context("removing manifest signature file")?
+        }
+        None => (),
+    }
+
+    let recorded = manifest::RecordedSource {
+        source: index.source.clone(),
+        signed: signature_content.is_some(),
+    };
+
+    std::fs::write(
+        config.manifest_source_file(),
+        serde_json::to_string_pretty(&recorded)?,
+    )
+    .context("writing manifest source file")?;
+
+    let release_file = config.manifest_release_file();
+
+    match &index.release {
+        Some(release) => {
+            std::fs::write(&release_file, release).context("writing manifest release file")?
+        }
+        None if release_file.exists() => {
+            std::fs::remove_file(&release_file).context("removing manifest release file")?
+        }
+        None => (),
+    }
+
+    Ok(())
+}
+
+async fn install_bundle(bundle: &Path, config: &Config) -> anyhow::Result<()> {
+    let work_dir = work_dir(config)?;
+
+    let file =
+        std::fs::File::open(bundle).with_context(|| format!("opening {}", bundle.display()))?;
+    tar::Archive::new(file)
+        .unpack(&work_dir)
+        .context("unpacking bundle")?;
+
+    let index: BundleIndex = serde_json::from_str(
+        &std::fs::read_to_string(work_dir.join(BUNDLE_INDEX)).context("reading bundle index")?,
+    )
+    .context("parsing bundle index")?;
+
+    match config.channel() {
+        Ok(channel) if channel != index.channel => anyhow::bail!(
+            "the bundle holds channel {}, not {channel}, run with `--channel {}`",
+            index.channel,
+            index.channel
+        ),
+        Ok(_) => (),
+        // a machine without a channel yet takes the one of the bundle
+        Err(_) => config.set_fixed_channel(&index.channel)?,
+    }
+
+    import_manifest(&index, &work_dir, config)?;
+
+    // the bundled archives match its overrides, not whatever this channel
+    // had before
+    overrides::reset_overrides(&[], config).await?;
+    overrides::save_overrides(&index.overrides, config).await?;
+
+    // archives go through the download cache, where offline installs find
+    // them; other targets are of no use on this machine. Nothing is evicted
+    // on the way, the install needs every one of them.
+    let target = config.target()?;
+
+    for asset in index.assets.iter() {
+        if asset.target != target {
+            continue;
+        }
+
+        let archive = work_dir.join(ASSETS_DIR).join(&asset.sha256);
+        let version = asset.version().context("invalid version in bundle")?;
+
+        checksum::verify_file(
+            &archive,
+            &[ExpectedDigest {
+                source: "bundle".to_string(),
+                hex: asset.sha256.clone(),
+            }],
+        )?;
+
+        cache::import(
+            &asset.tool,
+            &version,
+            &asset.target,
+            &asset.name,
+            &asset.url,
+            &archive,
+            config,
+        )?;
+    }
+
+    std::fs::remove_dir_all(&work_dir).context("cleaning up bundle work dir")?;
+
+    install::run(&install::Args::default(), &config.offline_copy()).await
+}
+
+pub async fn run(args: &Args, config: &Config) -> anyhow::Result<()> {
+    match &args.command {
+        Command::Create { output, targets } => create(output, targets, config).await,
+        Command::Install { bundle } => install_bundle(bundle, config).await,
+    }
+}
@@ -0,0 +1,103 @@
+use clap::{Parser, Subcommand};
+
+use crate::{ArgsCommon, Config, cache};
+
+#[derive(Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the cached release assets
+    List,
+    /// Drop broken entries and evict the least recently used assets past the
+    /// size limit
+    Prune,
+    /// Remove every cached asset
+    Clear,
+}
+
+impl ArgsCommon for Args {
+    fn skip_banner(&self) -> bool {
+        true
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+fn list(config: &Config) -> anyhow::Result<()> {
+    let mut index = cache::load_index(config)?;
+
+    if index.is_empty() {
+        println!("download cache is empty");
+        return Ok(());
+    }
+
+    index.sort_by(|a, b| (&a.tool, a.version()).cmp(&(&b.tool, b.version())));
+
+    for cached in index.iter() {
+        println!(
+            "{} {} ({}) {} {}",
+            cached.tool,
+            cached.version,
+            cached.target,
+            format_size(cache::blob_size(&cached.sha256, config)),
+            cached.url
+        );
+    }
+
+    let limit = config
+        .cache_size_limit()
+        .map(format_size)
+        .unwrap_or_else(|| "no limit".to_string());
+
+    println!();
+    println!(
+        "total: {} of {} in {}",
+        format_size(cache::total_size(&index, config)),
+        limit,
+        config.download_cache_dir().display()
+    );
+
+    Ok(())
+}
+
+fn prune(config: &Config) -> anyhow::Result<()> {
+    let evicted = cache::prune(config)?;
+
+    for cached in evicted.iter() {
+        println!(
+            "removed: {} {} ({})",
+            cached.tool, cached.version, cached.url
+        );
+    }
+
+    let index = cache::load_index(config)?;
+
+    println!(
+        "download cache holds {}",
+        format_size(cache::total_size(&index, config))
+    );
+
+    Ok(())
+}
+
+pub async fn run(args: &Args, config: &Config) -> anyhow::Result<()> {
+    match args.command {
+        Command::List => list(config),
+        Command::Prune => prune(config),
+        Command::Clear => {
+            cache::clear(config)?;
+            println!("removed: {}", config.download_cache_dir().display());
+            Ok(())
+        }
+    }
+}
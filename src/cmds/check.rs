@@ -2,7 +2,7 @@ use clap::Parser;
 use clap::ValueEnum;
 
 use crate::ArgsCommon;
-use crate::{Config, manifest, updates};
+use crate::{Config, cmds::install, manifest, updates};
 
 #[derive(Clone, Debug, ValueEnum)]
 pub enum OutputFormat {
@@ -25,6 +25,9 @@ pub struct Args {
 
     #[arg(short, long)]
     pub output: Option<OutputFormat>,
+
+    #[command(flatten)]
+    pub filter: updates::ToolFilter,
 }
 
 impl ArgsCommon for Args {
@@ -61,7 +64,7 @@ fn text_output(
         println!("You have {} update/s to install 📦", updates.len());
     } else {
         for update in updates {
-            print_update(&update, &manifest)?;
+            print_update(update, manifest)?;
         }
     }
 
@@ -75,9 +78,24 @@ fn json_output(updates: &[updates::Update]) -> anyhow::Result<()> {
 }
 
 pub async fn run(args: &Args, config: &Config) -> anyhow::Result<()> {
+    // a version would be silently ignored, updates always follow the manifest
+    if let Some(spec) = args.filter.tools.iter().find(|spec| spec.version.is_some()) {
+        anyhow::bail!(
+            "check doesn't take a version, use `tx3up install {}@<version>` to install one",
+            spec.name
+        );
+    }
+
     let manifest = manifest::load_latest_manifest(config, args.force).await?;
 
-    let updates = updates::load_updates(&manifest, config, args.force).await?;
+    let updates = updates::load_updates(
+        &manifest,
+        &args.filter,
+        install::DEFAULT_JOBS,
+        config,
+        args.force,
+    )
+    .await?;
 
     if args.silent {
         return Ok(());
@@ -0,0 +1,564 @@
+use clap::Parser;
+use semver::VersionReq;
+use serde::Serialize;
+
+use crate::{
+    ArgsCommon, Config, bin, checksum,
+    cmds::check::OutputFormat,
+    manifest::{self, Manifest, Tool},
+    perm_path,
+    receipts::{self, Receipt},
+    sources,
+};
+
+/// Remaining GitHub API calls under which installs are likely to be throttled
+const LOW_RATE_LIMIT: usize = 10;
+
+#[derive(Parser)]
+pub struct Args {
+    /// Also run every installed binary and hash it, to cross-check the
+    /// install receipts against what's actually in the channel
+    #[arg(long)]
+    pub verify: bool,
+
+    #[arg(short, long)]
+    pub output: Option<OutputFormat>,
+}
+
+impl ArgsCommon for Args {
+    fn skip_banner(&self) -> bool {
+        // the banner would create the default channel, hiding a missing one
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Outcome of a single check
+#[derive(Debug, Serialize)]
+struct Finding {
+    section: &'static str,
+    status: Status,
+    message: String,
+}
+
+#[derive(Default)]
+struct Report {
+    findings: Vec<Finding>,
+}
+
+impl Report {
+    fn push(&mut self, section: &'static str, status: Status, message: impl Into<String>) {
+        self.findings.push(Finding {
+            section,
+            status,
+            message: message.into(),
+        });
+    }
+
+    fn ok(&mut self, section: &'static str, message: impl Into<String>) {
+        self.push(section, Status::Ok, message);
+    }
+
+    fn warn(&mut self, section: &'static str, message: impl Into<String>) {
+        self.push(section, Status::Warn, message);
+    }
+
+    fn fail(&mut self, section: &'static str, message: impl Into<String>) {
+        self.push(section, Status::Fail, message);
+    }
+
+    fn failures(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.status == Status::Fail)
+            .count()
+    }
+}
+
+/// Checks the `default` symlink and which channel is active. Returns false
+/// when there's no channel to check the rest against.
+fn check_channel(report: &mut Report, config: &Config) -> bool {
+    const SECTION: &str = "channel";
+
+    let fixed_channel_dir = config.fixed_channel_dir();
+
+    match std::fs::symlink_metadata(&fixed_channel_dir) {
+        Err(_) => report.warn(
+            SECTION,
+            "no default channel set, pick one with `tx3up use <channel>`",
+        ),
+        Ok(metadata) if !metadata.file_type().is_symlink() => report.fail(
+            SECTION,
+            format!("{} is not a symlink", fixed_channel_dir.display()),
+        ),
+        Ok(_) if !fixed_channel_dir.exists() => report.fail(
+            SECTION,
+            format!(
+                "{} points to a missing channel dir, repoint it with `tx3up use <channel>`",
+                fixed_channel_dir.display()
+            ),
+        ),
+        Ok(_) => match config.fixed_channel() {
+            Ok(Some(channel)) => report.ok(SECTION, format!("default channel is {channel}")),
+            Ok(None) => (),
+            Err(err) => report.fail(SECTION, format!("can't read the default channel: {err}")),
+        },
+    }
+
+    match config.channel() {
+        Ok(channel) => {
+            report.ok(
+                SECTION,
+                format!(
+                    "active channel is {channel} (from {})",
+                    config.channel_origin()
+                ),
+            );
+
+            true
+        }
+        Err(err) => {
+            report.fail(SECTION, format!("no active channel: {err}"));
+            false
+        }
+    }
+}
+
+/// Checks the cached manifest of the active channel, returning it if usable
+async fn check_manifest(report: &mut Report, config: &Config) -> Option<Manifest> {
+    const SECTION: &str = "manifest";
+
+    let manifest = match manifest::load_local_manifest(config).await {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            report.fail(
+                SECTION,
+                format!(
+                    "no manifest at {}, run `tx3up install`",
+                    config.manifest_file().display()
+                ),
+            );
+
+            return None;
+        }
+        Err(err) => {
+            report.fail(SECTION, format!("unusable manifest: {err:#}"));
+            return None;
+        }
+    };
+
+    if config.manifest_signature_file().exists() {
+        report.ok(
+            SECTION,
+            format!("{} is signed and valid", config.manifest_file().display()),
+        );
+    } else {
+        report.warn(
+            SECTION,
+            format!(
+                "{} is valid but not signed",
+                config.manifest_file().display()
+            ),
+        );
+    }
+
+    match manifest::local_manifest_is_stale(config).await {
+        Ok(false) => (),
+        Ok(true) => report.warn(
+            SECTION,
+            "manifest is over a day old, `tx3up check --force` refreshes it",
+        ),
+        Err(err) => report.warn(SECTION, format!("can't tell the manifest age: {err}")),
+    }
+
+    Some(manifest)
+}
+
+/// Checks that a tool is installed, runnable and at a version the manifest
+/// accepts
+async fn check_tool(report: &mut Report, tool: &Tool, config: &Config) {
+    const SECTION: &str = "tools";
+
+    let name = &tool.name;
+
+    let bin_path = if tool.is_global {
+        match bin::find_all_on_path(name).into_iter().next() {
+            Some(path) => path,
+            None => {
+                report.fail(
+                    SECTION,
+                    format!(
+                        "{name}: global tool not found on PATH, {}",
+                        tool.instructions
+                    ),
+                );
+                return;
+            }
+        }
+    } else {
+        tool.bin_path(config)
+    };
+
+    if !bin_path.exists() {
+        report.fail(
+            SECTION,
+            format!("{name}: not installed, run `tx3up install {name}`"),
+        );
+        return;
+    }
+
+    if !bin::is_executable(&bin_path) {
+        report.fail(
+            SECTION,
+            format!("{name}: {} is not executable", bin_path.display()),
+        );
+        return;
+    }
+
+    let requested = match VersionReq::parse(&tool.version) {
+        Ok(requested) => requested,
+        Err(err) => {
+            report.fail(
+                SECTION,
+                format!("{name}: invalid version requirement: {err}"),
+            );
+            return;
+        }
+    };
+
+    match bin::check_version(tool, &bin_path).await {
+        Ok(version) if requested.matches(&version) => report.ok(
+            SECTION,
+            format!("{name} {version} at {}", bin_path.display()),
+        ),
+        Ok(version) => report.fail(
+            SECTION,
+            format!("{name}: version {version} doesn't match {requested}, run `tx3up install`"),
+        ),
+        Err(err) => report.fail(
+            SECTION,
+            format!("{name}: doesn't report its version: {err:#}"),
+        ),
+    }
+}
+
+/// Checks that running a tool by name reaches the copy managed by tx3up
+fn check_path(report: &mut Report, manifest: &Manifest, config: &Config) {
+    const SECTION: &str = "path";
+
+    let shims_dir = config.shims_dir();
+
+    let on_path = std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| bin::same_dir(&dir, &shims_dir))
+    });
+
+    if on_path {
+        report.ok(SECTION, format!("{} is on PATH", shims_dir.display()));
+    } else {
+        report.fail(
+            SECTION,
+            format!(
+                "{} is not on PATH, restart your shell or run `tx3up install`",
+                shims_dir.display()
+            ),
+        );
+    }
+
+    let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+
+    for tool in manifest.tools() {
+        if tool.is_global || Some(tool.name.as_str()) == self_name {
+            continue;
+        }
+
+        if let Some(shadowing) = bin::find_shadowing(&tool.name, config) {
+            report.fail(
+                SECTION,
+                format!(
+                    "{}: {} comes first on PATH, shadowing the tx3up one",
+                    tool.name,
+                    shadowing.display()
+                ),
+            );
+        }
+    }
+}
+
+/// Checks that some shell profile puts the shims on PATH
+fn check_shell(report: &mut Report, config: &Config) {
+    const SECTION: &str = "shell";
+
+    match perm_path::wired_profiles(config) {
+        Ok(profiles) if profiles.is_empty() => report.warn(
+            SECTION,
+            "no shell profile adds tx3up to PATH, `tx3up install` wires the existing ones",
+        ),
+        Ok(profiles) => {
+            for profile in profiles {
+                report.ok(SECTION, format!("{} adds tx3up to PATH", profile.display()));
+            }
+        }
+        Err(err) => report.warn(SECTION, format!("can't inspect shell profiles: {err}")),
+    }
+}
+
+/// Checks the GitHub token and how many API calls are left before installs
+/// get throttled
+async fn check_github(report: &mut Report, config: &Config) {
+    const SECTION: &str = "github";
+
+    if config.offline() {
+        report.ok(SECTION, "skipped, running offline");
+        return;
+    }
+
+    let authenticated = config.github_token().is_some();
+
+    let limit =
+        tokio::time::timeout(config.download_timeout(), sources::core_rate_limit(config)).await;
+
+    let rate = match limit {
+        Err(_) => {
+            report.warn(SECTION, "GitHub didn't answer in time");
+            return;
+        }
+        Ok(Err(err)) => {
+            // octocrab errors carry a whole backtrace, the cause is enough
+            report.warn(SECTION, format!("can't reach GitHub: {}", err.root_cause()));
+            return;
+        }
+        Ok(Ok(None)) => {
+            report.fail(
+                SECTION,
+                "GitHub rejects the token in GITHUB_TOKEN, renew or unset it",
+            );
+            return;
+        }
+        Ok(Ok(Some(rate))) => rate,
+    };
+
+    if authenticated {
+        report.ok(SECTION, "GITHUB_TOKEN is valid");
+    }
+
+    let quota = format!("{}/{} API calls left", rate.remaining, rate.limit);
+
+    if rate.remaining == 0 {
+        report.fail(
+            SECTION,
+            format!("{quota}, installs will fail until the limit resets"),
+        );
+    } else if rate.remaining < LOW_RATE_LIMIT {
+        report.warn(SECTION, format!("{quota}, installs may get throttled"));
+    } else {
+        report.ok(SECTION, quota);
+    }
+
+    if !authenticated {
+        report.warn(
+            SECTION,
+            "no GITHUB_TOKEN set, anonymous requests have a low rate limit",
+        );
+    }
+}
+
+/// Compares a receipt with the binary and extras found in the channel
+async fn check_receipt(
+    report: &mut Report,
+    name: &str,
+    receipt: &Receipt,
+    tool: Option<&Tool>,
+    verify: bool,
+    config: &Config,
+) {
+    const SECTION: &str = "receipts";
+
+    // a warning is no match either
+    let before = report.findings.len();
+
+    let bin_path = config.bin_dir().join(bin::file_name(name));
+
+    if !bin_path.exists() {
+        report.fail(
+            SECTION,
+            format!("{name}: binary missing at {}", bin_path.display()),
+        );
+        return;
+    }
+
+    for extra in receipt.extras.iter() {
+        if !config.channel_dir().join(extra).exists() {
+            report.fail(
+                SECTION,
+                format!("{name}: extra file {} is missing", extra.display()),
+            );
+        }
+    }
+
+    if verify {
+        match (&receipt.binary_sha256, checksum::file_digest(&bin_path)) {
+            (_, Err(err)) => report.fail(SECTION, format!("{name}: can't hash binary: {err}")),
+            (None, Ok(_)) => report.warn(
+                SECTION,
+                format!("{name}: no binary digest recorded, reinstall to record one"),
+            ),
+            (Some(expected), Ok(actual)) if !expected.eq_ignore_ascii_case(&actual) => report.fail(
+                SECTION,
+                format!("{name}: binary digest {actual} doesn't match the recorded {expected}"),
+            ),
+            (Some(_), Ok(_)) => (),
+        }
+
+        // the manifest knows how to ask the tool for its version
+        match tool {
+            None => report.warn(
+                SECTION,
+                format!("{name}: not part of the manifest, skipping the version check"),
+            ),
+            Some(tool) => match (receipt.version(), bin::check_version(tool, &bin_path).await) {
+                (_, Err(err)) => report.fail(
+                    SECTION,
+                    format!("{name}: binary doesn't report its version: {err}"),
+                ),
+                (None, Ok(_)) => report.warn(
+                    SECTION,
+                    format!("{name}: no version recorded, reinstall to record one"),
+                ),
+                (Some(recorded), Ok(reported)) if recorded != reported => report.fail(
+                    SECTION,
+                    format!(
+                        "{name}: binary reports version {reported}, the receipt says {recorded}"
+                    ),
+                ),
+                (Some(_), Ok(_)) => (),
+            },
+        }
+    }
+
+    if report.findings.len() == before {
+        report.ok(SECTION, format!("{name}: matches its receipt"));
+    }
+}
+
+async fn check_receipts(
+    report: &mut Report,
+    manifest: Option<&Manifest>,
+    verify: bool,
+    config: &Config,
+) {
+    const SECTION: &str = "receipts";
+
+    let receipts = match receipts::load_receipts(config) {
+        Ok(receipts) => receipts,
+        Err(err) => {
+            report.fail(SECTION, format!("{err:#}"));
+            return;
+        }
+    };
+
+    let mut names: Vec<_> = receipts.keys().collect();
+    names.sort();
+
+    for name in names {
+        let tool = manifest.and_then(|m| m.tool_by_name(name));
+        check_receipt(report, name, &receipts[name], tool, verify, config).await;
+    }
+
+    // tools installed by older versions of tx3up (or by hand) have no receipt
+    let Some(manifest) = manifest else {
+        return;
+    };
+
+    let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+
+    for tool in manifest.tools() {
+        if tool.is_global || Some(tool.name.as_str()) == self_name {
+            continue;
+        }
+
+        if !receipts.contains_key(&tool.name) && tool.bin_path(config).exists() {
+            report.warn(
+                SECTION,
+                format!(
+                    "{}: installed without a receipt, reinstall to record one",
+                    tool.name
+                ),
+            );
+        }
+    }
+}
+
+fn text_output(report: &Report) {
+    let mut section = "";
+
+    for finding in report.findings.iter() {
+        if finding.section != section {
+            section = finding.section;
+            println!("\n{section}:");
+        }
+
+        let icon = match finding.status {
+            Status::Ok => "✅",
+            Status::Warn => "⚠️ ",
+            Status::Fail => "❌",
+        };
+
+        println!("  {icon} {}", finding.message);
+    }
+
+    println!();
+}
+
+fn json_output(report: &Report) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(&report.findings)?;
+    println!("{}", json);
+    Ok(())
+}
+
+pub async fn run(args: &Args, config: &Config) -> anyhow::Result<()> {
+    let mut report = Report::default();
+
+    if check_channel(&mut report, config) {
+        let manifest = check_manifest(&mut report, config).await;
+
+        if let Some(manifest) = &manifest {
+            let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+
+            // tx3up itself is not installed into the channel
+            for tool in manifest.tools() {
+                if Some(tool.name.as_str()) != self_name {
+                    check_tool(&mut report, tool, config).await;
+                }
+            }
+
+            check_path(&mut report, manifest, config);
+        }
+
+        check_receipts(&mut report, manifest.as_ref(), args.verify, config).await;
+    }
+
+    check_shell(&mut report, config);
+    check_github(&mut report, config).await;
+
+    let output = args.output.as_ref().unwrap_or(&OutputFormat::Text);
+
+    match output {
+        OutputFormat::Json => json_output(&report)?,
+        OutputFormat::Text => text_output(&report),
+    };
+
+    let failures = report.failures();
+
+    if failures > 0 {
+        anyhow::bail!("{failures} problem/s found");
+    }
+
+    Ok(())
+}
@@ -1,29 +1,54 @@
 use anyhow::{Context, Result};
 use clap::Parser;
-use flate2::read::GzDecoder;
-use octocrab::models::repos::Asset;
-use octocrab::models::repos::Release;
-use reqwest::Client;
+use futures_util::{StreamExt, stream};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use semver::Version;
 use semver::VersionReq;
-use serde::{Deserialize, Serialize};
-use std::fs;
-use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
-use tar::Archive;
-use xz2::read::XzDecoder;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::ArgsCommon;
+use crate::archive;
+use crate::bin;
+use crate::cache;
+use crate::checksum::{self, ExpectedDigest};
+use crate::cmds;
+use crate::download::{self, DownloadOptions};
 use crate::manifest;
+use crate::overrides;
 use crate::perm_path;
+use crate::receipts;
+use crate::shims;
+use crate::sources::{self, Asset, Release, ReleaseSource};
+use crate::staging::Transaction;
 use crate::updates;
 use crate::{Config, manifest::*};
 
+pub const DEFAULT_JOBS: usize = 4;
+
 #[derive(Parser, Default)]
 pub struct Args {
     #[arg(long)]
     release: Option<String>,
+
+    /// Number of tools to download and extract in parallel
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    #[command(flatten)]
+    filter: updates::ToolFilter,
+
+    /// Drop the version overrides of the named tools (or of every tool) and
+    /// go back to the manifest requirement
+    #[arg(long)]
+    reset: bool,
+}
+
+impl Args {
+    fn jobs(&self) -> usize {
+        self.jobs.unwrap_or(DEFAULT_JOBS).max(1)
+    }
 }
 
 impl ArgsCommon for Args {
@@ -32,148 +57,209 @@ impl ArgsCommon for Args {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct VersionsFile {
-    tools: Vec<ToolVersion>,
-}
+fn find_arch_asset(
+    tool: &Tool,
+    target: &str,
+    version: &Version,
+    release: &Release,
+) -> Option<Asset> {
+    // an explicit name beats guessing
+    if let Some(name) = tool.asset_name(target, version) {
+        return release.asset_by_name(&name).cloned();
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ToolVersion {
-    repo_name: String,
-    repo_owner: String,
-    version: String,
+    let target = format!("{}-{target}", tool.name);
+
+    release
+        .assets
+        .iter()
+        .filter(|asset| !asset.name.ends_with(".sha256"))
+        .find(|asset| asset.name.contains(&target))
+        .cloned()
 }
 
-pub async fn download_binary(url: &str, path: &PathBuf) -> Result<()> {
-    let client = Client::new();
-    let mut response = client.get(url).send().await?;
-    let total_size = response.content_length().unwrap_or(0);
-    let mut file = fs::File::create(path)?;
-    let mut downloaded = 0;
-
-    while let Some(chunk_result) = response.chunk().await? {
-        file.write_all(&chunk_result)?;
-        downloaded += chunk_result.len() as u64;
-
-        if total_size > 0 {
-            let progress = (downloaded as f64 / total_size as f64) * 100.0;
-            print!("\r> Downloading: {progress:.1}% ({downloaded}/{total_size})");
-            std::io::stdout().flush()?;
-        }
+async fn collect_expected_digests(
+    tool: &Tool,
+    target: &str,
+    release: &Release,
+    asset: &Asset,
+    config: &Config,
+) -> Result<Vec<ExpectedDigest>> {
+    let mut expected = vec![];
+
+    if let Some(pinned) = tool.pinned_digest(target) {
+        expected.push(ExpectedDigest {
+            source: "manifest".to_string(),
+            hex: pinned.to_string(),
+        });
     }
 
-    println!(); // New line after progress
+    if let Some(published) = checksum::find_release_digest(release, asset, config).await? {
+        expected.push(published);
+    }
 
-    Ok(())
+    Ok(expected)
 }
 
-// Find the first file that starts with the tool name
-fn extract_binary_main_entry<R: std::io::Read>(
-    mut archive: Archive<R>,
-    install_dir: &Path,
-    tool_name: &str,
-) -> anyhow::Result<()> {
-    for entry in archive.entries()? {
-        let mut entry = entry?;
-        let path = entry.path()?;
-        let filename = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .context("Invalid filename in archive")?;
-
-        if filename.eq(tool_name) && entry.header().entry_type().is_file() {
-            let binary_path = install_dir.join(filename);
-            entry.unpack(&binary_path)?;
-
-            // Make the binary executable
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let mut perms = fs::metadata(&binary_path)?.permissions();
-                perms.set_mode(0o755);
-                fs::set_permissions(&binary_path, perms)?;
-            }
+/// Downloads and verifies a release asset into the stage dir, returning the
+/// path of the archive.
+async fn download_asset(
+    asset: &Asset,
+    expected: &[ExpectedDigest],
+    stage_dir: &Path,
+    config: &Config,
+    progress: &ProgressBar,
+) -> Result<PathBuf> {
+    progress.set_message(format!("downloading {}", asset.name));
 
-            return Ok(());
-        }
+    let partial = download::partial_path(&asset.url, config);
+    std::fs::create_dir_all(config.partial_downloads_dir())
+        .context("creating partial downloads dir")?;
+
+    // held until the download has left the partial downloads dir
+    let _lock = download::lock_partial(&partial, progress)?;
+
+    let options = DownloadOptions::from_config(config);
+    download::download_file(&asset.url, &partial, &options, progress).await?;
+
+    // only a complete download leaves the partial downloads dir
+    let archive_path = stage_dir.join(&asset.name);
+
+    if std::fs::rename(&partial, &archive_path).is_err() {
+        std::fs::copy(&partial, &archive_path).context("moving download to stage dir")?;
+        std::fs::remove_file(&partial).context("removing partial download")?;
+    }
+
+    if expected.is_empty() {
+        log(
+            progress,
+            format!(
+                "> No checksum published for {}, skipping verification",
+                asset.name
+            ),
+        );
+    } else {
+        progress.set_message("verifying checksum");
+        checksum::verify_file(&archive_path, expected)?;
     }
 
-    anyhow::bail!("No matching binary found in archive")
+    Ok(archive_path)
 }
 
-fn extract_binary(path: &Path, install_dir: &Path, tool_name: &str) -> Result<()> {
-    let file = fs::File::open(path)?;
+/// Files of a tool ready to be moved into the channel
+pub struct StagedTool {
+    binary: PathBuf,
 
-    let extension = path.extension().and_then(|s| s.to_str());
+    /// Staged extra files, along with their path relative to the channel dir
+    extras: Vec<(PathBuf, PathBuf)>,
 
-    match extension {
-        Some("gz") => {
-            let archive = Archive::new(GzDecoder::new(file));
-            extract_binary_main_entry(archive, install_dir, tool_name)?;
-        }
-        Some("xz") => {
-            let archive = Archive::new(XzDecoder::new(file));
-            extract_binary_main_entry(archive, install_dir, tool_name)?;
-        }
-        _ => anyhow::bail!("Unsupported archive format. Expected .gz or .xz"),
-    };
+    /// Version the staged binary reports
+    version: Version,
 
-    Ok(())
+    /// Tag of the release the asset belongs to, if known
+    release: Option<String>,
+
+    asset: Asset,
+
+    /// SHA-256 digest of the asset
+    sha256: String,
 }
 
-fn find_arch_asset(tool_name: &str, release: Release) -> Option<Asset> {
-    let arch = std::env::consts::ARCH;
-    let os = std::env::consts::OS;
+impl StagedTool {
+    pub fn binary(&self) -> &Path {
+        &self.binary
+    }
+}
 
-    // Map Rust's arch to our convention
-    let arch = match arch {
-        "x86_64" => "x86_64",
-        "aarch64" => "aarch64",
-        _ => return None,
-    };
+/// Extracts a tool from an archive of `asset` in its stage dir and checks the
+/// version it reports.
+async fn stage_archive(
+    tool: &Tool,
+    archive_path: PathBuf,
+    release: Option<&str>,
+    asset: &Asset,
+    stage_dir: &Path,
+    requested: &VersionReq,
+    progress: &ProgressBar,
+) -> Result<StagedTool> {
+    let sha256 = checksum::file_digest(&archive_path)?;
 
-    // Map Rust's OS to our convention
-    let os = match os {
-        "macos" => "apple-darwin",
-        "linux" => "unknown-linux-gnu",
-        _ => return None,
-    };
+    progress.set_message("extracting");
 
-    let target = format!("{tool_name}-{arch}-{os}");
+    let binary_name = tool.bin_file_name();
+    let extract_dir = stage_dir.to_path_buf();
+    let format = tool.format;
+    let extras = tool.extras.clone();
 
-    release
-        .assets
-        .iter()
-        .find(|asset| asset.name.contains(&target))
-        .cloned()
-}
+    let staged_extras = tokio::task::spawn_blocking(move || {
+        archive::extract_binary(&archive_path, format, &extract_dir, &binary_name, &extras)
+    })
+    .await??;
 
-pub async fn download_tool_from_asset(tool: &Tool, asset: &Asset, config: &Config) -> Result<()> {
-    println!("> Downloading binary: {}", asset.name);
+    let staged_binary = stage_dir.join(tool.bin_file_name());
 
-    // Create installation directory
-    let install_dir = config.bin_dir().clone();
-    fs::create_dir_all(&install_dir)?;
+    progress.set_message("checking version");
+    let staged_version = bin::check_version(tool, &staged_binary)
+        .await
+        .context("staged binary doesn't report its version")?;
 
-    // Download the binary
-    let binary_path = install_dir.join(&asset.name);
-    download_binary(asset.browser_download_url.as_ref(), &binary_path).await?;
+    if !requested.matches(&staged_version) {
+        anyhow::bail!(
+            "staged binary reports version {staged_version}, which doesn't match {requested}"
+        );
+    }
 
-    println!("> Extracting binary...");
-    extract_binary(&binary_path, &install_dir, &tool.name)?;
+    let extras = staged_extras
+        .into_iter()
+        .map(|relative| {
+            (
+                stage_dir.join(archive::EXTRAS_DIR).join(&relative),
+                relative,
+            )
+        })
+        .collect();
+
+    Ok(StagedTool {
+        binary: staged_binary,
+        extras,
+        version: staged_version,
+        release: release.map(str::to_string),
+        asset: asset.clone(),
+        sha256,
+    })
+}
 
-    // Clean up the tar.gz file
-    fs::remove_file(&binary_path)?;
+/// Returns the version when the requirement pins a single exact release
+fn exact_version(requested: &VersionReq) -> Option<Version> {
+    let [comparator] = requested.comparators.as_slice() else {
+        return None;
+    };
 
-    println!(
-        "Successfully installed {} to {}",
-        tool.name,
-        install_dir.join(&tool.name).display()
-    );
+    if comparator.op != semver::Op::Exact {
+        return None;
+    }
 
-    println!();
+    Some(Version {
+        major: comparator.major,
+        minor: comparator.minor?,
+        patch: comparator.patch?,
+        pre: comparator.pre.clone(),
+        build: semver::BuildMetadata::EMPTY,
+    })
+}
 
-    Ok(())
+fn wants_prerelease(requested: &VersionReq) -> bool {
+    requested.comparators.iter().any(|c| !c.pre.is_empty())
+}
+
+async fn find_release_by_tag(source: &dyn ReleaseSource, version: &Version) -> Option<Release> {
+    for tag in [format!("v{version}"), version.to_string()] {
+        if let Ok(Some(release)) = source.release_by_tag(&tag).await {
+            return Some(release);
+        }
+    }
+
+    None
 }
 
 async fn find_matching_release(
@@ -181,120 +267,692 @@ async fn find_matching_release(
     requested: &VersionReq,
     config: &Config,
 ) -> anyhow::Result<Option<(Version, Release)>> {
-    let octocrab = crate::manifest::build_octocrab(config)?;
-    let owner = tool.repo_owner.clone();
-    let repo = tool.repo_name.clone();
-    let repo = octocrab.repos(owner, repo);
-
-    let mut page = repo
-        .releases()
-        .list()
-        .send()
-        .await
-        .context("Failed to list releases")?;
-
-    for release in page.take_items() {
-        let sanitized = if release.tag_name.starts_with("v") {
-            release.tag_name[1..].to_string()
-        } else {
-            release.tag_name.clone()
-        };
+    let source = sources::open(&tool.source(config), config)?;
 
-        let Ok(version) = Version::parse(&sanitized) else {
-            continue;
-        };
+    select_release(source.as_ref(), requested).await
+}
 
-        if requested.matches(&version) {
-            return Ok(Some((version, release)));
-        }
+/// Picks the newest release matching `requested`, skipping drafts, and
+/// prereleases unless the requirement asks for one
+async fn select_release(
+    source: &dyn ReleaseSource,
+    requested: &VersionReq,
+) -> anyhow::Result<Option<(Version, Release)>> {
+    let allow_prerelease = wants_prerelease(requested);
+
+    let acceptable =
+        |release: &Release| !release.draft && (allow_prerelease || !release.prerelease);
+
+    // no need to walk every release when the requirement names one
+    if let Some(exact) = exact_version(requested)
+        && let Some(release) = find_release_by_tag(source, &exact).await
+        && acceptable(&release)
+        && let Some(version) = release.version()
+        && requested.matches(&version)
+    {
+        return Ok(Some((version, release)));
     }
 
-    Ok(None)
+    let releases = source.list_releases().await?;
+
+    let best = releases
+        .into_iter()
+        .filter(acceptable)
+        .filter_map(|release| Some((release.version()?, release)))
+        .filter(|(version, _)| requested.matches(version))
+        .max_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(best)
 }
 
-async fn run_github_release_installer(
+/// A release asset picked for a tool on a given target
+pub struct ToolAsset {
+    pub version: Version,
+    pub target: String,
+    pub release: Release,
+    pub asset: Asset,
+}
+
+/// Finds the newest release of a tool matching `requested` and its asset for
+/// `target`.
+pub async fn resolve_asset(
     tool: &Tool,
     requested: &VersionReq,
+    target: &str,
     config: &Config,
-) -> anyhow::Result<()> {
-    println!("\n> Installing {} at version {}", tool.name, requested);
-
+) -> anyhow::Result<ToolAsset> {
     let Some((version, release)) = find_matching_release(tool, requested, config).await? else {
         return Err(anyhow::anyhow!("No release found for {}", tool.name));
     };
 
-    let Some(asset) = find_arch_asset(&tool.name, release) else {
-        return Err(anyhow::anyhow!("No asset found for {}", tool.name));
+    let Some(asset) = find_arch_asset(tool, target, &version, &release) else {
+        return Err(anyhow::anyhow!(
+            "No asset found for {} on {target}",
+            tool.name
+        ));
     };
 
-    println!("\nFound version of {} to install 🎉", tool.name);
-    println!("  Version: {version}");
-    println!("  Asset: {}", asset.name);
+    Ok(ToolAsset {
+        version,
+        target: target.to_string(),
+        release,
+        asset,
+    })
+}
 
-    download_tool_from_asset(tool, &asset, config).await?;
+/// Gets a verified copy of a release asset into `dir`, from the download
+/// cache when possible, returning the path of the archive.
+pub async fn fetch_asset(
+    tool: &Tool,
+    picked: &ToolAsset,
+    dir: &Path,
+    config: &Config,
+    progress: &ProgressBar,
+) -> anyhow::Result<PathBuf> {
+    let ToolAsset {
+        version,
+        target,
+        release,
+        asset,
+    } = picked;
+
+    let expected = collect_expected_digests(tool, target, release, asset, config).await?;
+
+    // another channel (or a previous install) may have fetched it already
+    if let Some(cached) = cache::find_by_url(&asset.url, &expected, config)? {
+        log(
+            progress,
+            format!("> Reusing {} from the download cache", asset.name),
+        );
+
+        // another process may evict it in the meantime, the network still
+        // has it
+        match copy_from_cache(&cached, &expected, dir, config, progress) {
+            Ok(archive) => return Ok(archive),
+            Err(err) => log(
+                progress,
+                format!("> Couldn't reuse {}, downloading it: {err}", asset.name),
+            ),
+        }
+    }
 
-    Ok(())
+    let archive = download_asset(asset, &expected, dir, config, progress).await?;
+
+    // the cache is a convenience, an install shouldn't fail because of it
+    if let Err(err) = cache::store(
+        &tool.name,
+        version,
+        target,
+        &asset.name,
+        &asset.url,
+        &archive,
+        config,
+    ) {
+        log(
+            progress,
+            format!(
+                "> Couldn't keep {} in the download cache: {err}",
+                asset.name
+            ),
+        );
+    }
+
+    Ok(archive)
+}
+
+async fn run_github_release_installer(
+    tool: &Tool,
+    requested: &VersionReq,
+    tx: &Transaction,
+    config: &Config,
+    progress: &ProgressBar,
+) -> anyhow::Result<Option<StagedTool>> {
+    progress.set_message(format!("looking for a release matching {requested}"));
+
+    let target = config.target()?;
+
+    let picked = resolve_asset(tool, requested, &target, config).await?;
+
+    log(
+        progress,
+        format!(
+            "> Found version {} of {} to install 🎉 ({})",
+            picked.version, tool.name, picked.asset.name
+        ),
+    );
+
+    // everything happens in a stage dir so that a failure never leaves a
+    // half-written binary in the channel's bin dir
+    let stage_dir = tx.stage_dir(&tool.name)?;
+
+    let archive = fetch_asset(tool, &picked, &stage_dir, config, progress).await?;
+
+    let staged = stage_archive(
+        tool,
+        archive,
+        Some(&picked.release.tag),
+        &picked.asset,
+        &stage_dir,
+        requested,
+        progress,
+    )
+    .await?;
+
+    Ok(Some(staged))
+}
+
+/// Copies a cached asset into the stage dir and checks it against its cached
+/// digest and any other expected one, returning the path of the archive.
+fn copy_from_cache(
+    cached: &cache::CachedAsset,
+    expected: &[ExpectedDigest],
+    stage_dir: &Path,
+    config: &Config,
+    progress: &ProgressBar,
+) -> Result<PathBuf> {
+    progress.set_message(format!("copying {} from cache", cached.name));
+
+    let archive = stage_dir.join(&cached.name);
+
+    cache::copy_out(cached, &archive, config)?;
+
+    // the blob could have been corrupted since it was stored
+    let mut expected = expected.to_vec();
+    expected.push(ExpectedDigest {
+        source: "download cache".to_string(),
+        hex: cached.sha256.clone(),
+    });
+
+    progress.set_message("verifying checksum");
+    checksum::verify_file(&archive, &expected)?;
+
+    Ok(archive)
+}
+
+/// Installs a tool from the download cache, without touching the network
+async fn run_cached_installer(
+    tool: &Tool,
+    requested: &VersionReq,
+    tx: &Transaction,
+    config: &Config,
+    progress: &ProgressBar,
+) -> anyhow::Result<Option<StagedTool>> {
+    let target = config.target()?;
+
+    let cached = cache::find_asset(&tool.name, requested, &target, config)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "offline: no cached download of {} matching {requested} for {target} in {}",
+            tool.name,
+            config.download_cache_dir().display()
+        )
+    })?;
+
+    log(
+        progress,
+        format!(
+            "> Found cached version {} of {} to install 🎉 ({})",
+            cached.version, tool.name, cached.name
+        ),
+    );
+
+    let expected: Vec<_> = tool
+        .pinned_digest(&target)
+        .map(|pinned| ExpectedDigest {
+            source: "manifest".to_string(),
+            hex: pinned.to_string(),
+        })
+        .into_iter()
+        .collect();
+
+    let stage_dir = tx.stage_dir(&tool.name)?;
+    let archive = copy_from_cache(&cached, &expected, &stage_dir, config, progress)?;
+
+    // the cache doesn't know which release the asset came from
+    let asset = Asset {
+        name: cached.name.clone(),
+        url: cached.url.clone(),
+    };
+
+    let staged =
+        stage_archive(tool, archive, None, &asset, &stage_dir, requested, progress).await?;
+
+    Ok(Some(staged))
 }
 
 async fn run_instructions_installer(
     tool: &Tool,
     requested: &VersionReq,
-    _: &Config,
-) -> anyhow::Result<()> {
-    println!(
-        "\n> Please follow the instructions to install {} at version {}",
-        tool.name, requested
+    progress: &ProgressBar,
+) -> anyhow::Result<Option<StagedTool>> {
+    log(
+        progress,
+        format!(
+            "\n> Please follow the instructions to install {} at version {}\n\n  {}\n",
+            tool.name, requested, tool.instructions
+        ),
     );
 
-    println!();
-    println!("  {}", tool.instructions);
-    println!();
+    Ok(None)
+}
+
+/// Prepares a tool for installation. Returns the staged files, if the
+/// installer produced any.
+pub async fn stage_tool(
+    tool: &Tool,
+    requested: &VersionReq,
+    tx: &Transaction,
+    config: &Config,
+    progress: &ProgressBar,
+) -> anyhow::Result<Option<StagedTool>> {
+    match tool.installer {
+        Installer::GithubRelease if config.offline() => {
+            run_cached_installer(tool, requested, tx, config, progress).await
+        }
+        Installer::GithubRelease => {
+            run_github_release_installer(tool, requested, tx, config, progress).await
+        }
+        Installer::Instructions => run_instructions_installer(tool, requested, progress).await,
+    }
+}
+
+/// Set by commands whose stdout belongs to something else, such as `run`
+static REPORT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Sends the progress of every later install to stderr
+pub fn report_to_stderr() {
+    REPORT_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Prints a line about the progress of an install
+fn report(msg: impl std::fmt::Display) {
+    if REPORT_TO_STDERR.load(Ordering::Relaxed) {
+        eprintln!("{msg}");
+    } else {
+        println!("{msg}");
+    }
+}
+
+/// Prints above the progress bars, falling back to plain output when they're
+/// hidden (e.g. stdout is not a terminal) since indicatif drops the message
+fn log(progress: &ProgressBar, msg: String) {
+    if progress.is_hidden() {
+        report(msg);
+    } else {
+        progress.println(msg);
+    }
+}
+
+pub fn tool_progress(multi: &MultiProgress, tool: &Tool) -> ProgressBar {
+    let style = ProgressStyle::with_template(
+        "{prefix:>12.bold} [{bar:30}] {bytes:>10}/{total_bytes:<10} {msg}",
+    )
+    .unwrap()
+    .progress_chars("=> ");
+
+    let progress = multi.add(ProgressBar::new(0));
+    progress.set_style(style);
+    progress.set_prefix(tool.name.clone());
+    progress.set_message("waiting");
+
+    progress
+}
+
+fn print_update(update: &updates::Update, tool: &Tool) -> anyhow::Result<()> {
+    if let Some(current) = update.current()? {
+        report(format!(
+            "\nYour version of {} needs to be updated 😬",
+            tool.name
+        ));
+        report(format!("  Current version: {current}"));
+        report(format!("  Requested version: {}", update.requested));
+    } else {
+        report(format!("\nYour need to install {} 📦", tool.name));
+    }
 
     Ok(())
 }
 
-async fn install_tool(tool: &Tool, requested: &VersionReq, config: &Config) -> anyhow::Result<()> {
-    match tool.installer {
-        Installer::GithubRelease => run_github_release_installer(tool, requested, config).await,
-        Installer::Instructions => run_instructions_installer(tool, requested, config).await,
+/// Downloads and installs the given updates, keeping the channel untouched if
+/// any of them fails.
+pub async fn install_updates(
+    manifest: &Manifest,
+    updates: &[updates::Update],
+    jobs: usize,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let mut pending = vec![];
+
+    for update in updates.iter() {
+        let tool = manifest.tool_by_name(&update.tool).unwrap();
+        print_update(update, tool)?;
+        pending.push((tool, update.requested()?));
+    }
+
+    report("");
+
+    let mut tx = Transaction::begin(config)?;
+    let multi = MultiProgress::new();
+
+    // download and extract in parallel, nothing touches the bin dir yet
+    let staged: Vec<_> = stream::iter(pending)
+        .map(|(tool, requested)| {
+            let tx = &tx;
+            let progress = tool_progress(&multi, tool);
+
+            async move {
+                let staged = stage_tool(tool, &requested, tx, config, &progress).await;
+
+                match &staged {
+                    Ok(_) => progress.finish_with_message("ready"),
+                    Err(err) => progress.abandon_with_message(format!("failed: {err}")),
+                }
+
+                (tool, staged)
+            }
+        })
+        .buffer_unordered(jobs)
+        .collect()
+        .await;
+
+    let mut ready = vec![];
+
+    for (tool, staged) in staged {
+        match staged {
+            Ok(Some(staged)) => ready.push((tool, staged)),
+            Ok(None) => (),
+            Err(err) => {
+                report(format!(
+                    "\nFailed to install {}, rolling back 😬",
+                    tool.name
+                ));
+                tx.rollback()?;
+                return Err(err);
+            }
+        }
+    }
+
+    // swap every staged file into place, restoring the previous ones if any
+    // of the moves fails
+    for (tool, staged) in ready.iter() {
+        let install_path = config.bin_dir().join(tool.bin_file_name());
+
+        let moved = tx.replace(&staged.binary, &install_path).and_then(|_| {
+            staged.extras.iter().try_for_each(|(file, relative)| {
+                tx.replace(file, &config.channel_dir().join(relative))
+            })
+        });
+
+        if let Err(err) = moved {
+            report(format!(
+                "\nFailed to install {}, rolling back 😬",
+                tool.name
+            ));
+            tx.rollback()?;
+            return Err(err);
+        }
+
+        report(format!(
+            "Successfully installed {} to {}",
+            tool.name,
+            install_path.display()
+        ));
+    }
+
+    tx.commit()?;
+
+    // files the previous versions installed but these don't are stale now
+    for (tool, staged) in ready {
+        let binary_sha256 = checksum::file_digest(&config.bin_dir().join(tool.bin_file_name()))?;
+
+        let receipt = receipts::Receipt {
+            version: Some(staged.version.to_string()),
+            release: staged.release,
+            asset: Some(staged.asset.name),
+            source: Some(staged.asset.url),
+            sha256: Some(staged.sha256),
+            binary_sha256: Some(binary_sha256),
+            installed_at: cache::now(),
+            extras: staged
+                .extras
+                .into_iter()
+                .map(|(_, relative)| relative)
+                .collect(),
+        };
+
+        receipts::replace_receipt(&tool.name, receipt, config)?;
+    }
+
+    Ok(())
+}
+
+/// Points out tools that run from another copy on PATH than the one just
+/// installed
+async fn warn_shadowed(manifest: &Manifest, filter: &updates::ToolFilter, config: &Config) {
+    let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+
+    for tool in manifest.tools().filter(|tool| filter.matches(tool)) {
+        if Some(tool.name.as_str()) != self_name {
+            shims::warn_if_shadowed(tool, config).await;
+        }
     }
 }
 
 pub async fn run(args: &Args, config: &Config) -> anyhow::Result<()> {
-    let manifest = match &args.release {
+    config.ensure_project()?;
+
+    if args.reset {
+        let names: Vec<_> = args.filter.tools.iter().map(|t| t.name.clone()).collect();
+        overrides::reset_overrides(&names, config).await?;
+    }
+
+    let release = args.release.as_deref().or(config.pinned_release());
+
+    let mut manifest = match release {
         Some(release) => manifest::load_tagged_manifest(config, release).await?,
         None => manifest::load_latest_manifest(config, true).await?,
     };
 
-    let updates = updates::check_updates(&manifest, config).await?;
+    // versions requested as `<tool>@<version>` only become overrides once
+    // they're installed
+    let requested = args.filter.requested_versions();
+
+    // unknown tools are reported by the filter validation below
+    manifest.apply_overrides(&requested);
+
+    let updates = updates::check_updates(&manifest, &args.filter, args.jobs(), config).await?;
 
     if updates.is_empty() {
         println!("You are up to date 🎉");
+        overrides::save_overrides(&requested, config).await?;
+        shims::install_shims(&manifest, config)?;
+        warn_shadowed(&manifest, &args.filter, config).await;
         return Ok(());
     }
 
-    for update in updates {
-        let tool = manifest.tool_by_name(&update.tool).unwrap();
+    // the running executable is replaced in place rather than installed into
+    // the channel
+    let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+    let (self_updates, updates): (Vec<_>, Vec<_>) = updates
+        .into_iter()
+        .partition(|update| Some(update.tool.as_str()) == self_name);
 
-        if let Some(current) = update.current()? {
-            println!("\nYour version of {} needs to be updated 😬", tool.name);
-            println!("  Current version: {current}");
-            println!("  Requested version: {}", update.requested);
-        } else {
-            println!("\nYour need to install {} 📦", tool.name);
-        }
+    if !updates.is_empty() {
+        install_updates(&manifest, &updates, args.jobs(), config).await?;
+    }
 
-        install_tool(tool, &update.requested()?, config).await?;
+    if let Some(tool) = manifest.self_tool()
+        && !self_updates.is_empty()
+    {
+        cmds::self_::update_self(tool, config).await?;
     }
 
+    overrides::save_overrides(&requested, config).await?;
+
     // we do a second check to make sure we have the latest updates
-    let after = updates::check_updates(&manifest, config).await?;
+    let after = updates::check_updates(&manifest, &args.filter, args.jobs(), config).await?;
 
     if !after.is_empty() {
         println!("Seems that you still have updates to install",);
     }
 
+    shims::install_shims(&manifest, config)?;
+
     perm_path::check_or_update(config)?;
 
+    warn_shadowed(&manifest, &args.filter, config).await;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+
+    const TAGS: &[&str] = &[
+        "v0.10.0",
+        "v0.11.2",
+        "v0.11.3",
+        "v0.12.0",
+        "v0.12.3",
+        "v0.13.0-beta.1",
+        "nightly",
+    ];
+
+    /// A tool whose releases sit in a local dir, one per tag
+    fn local_tool(dir: &Path) -> Tool {
+        for tag in TAGS {
+            std::fs::create_dir_all(dir.join(tag)).unwrap();
+            std::fs::write(dir.join(tag).join("trix.tar.gz"), tag).unwrap();
+        }
+
+        serde_json::from_value(serde_json::json!({
+            "name": "trix",
+            "description": "",
+            "repo_owner": "tx3-lang",
+            "repo_name": "trix",
+            "version": "^0.12",
+            "source": {"type": "local_dir", "path": dir},
+        }))
+        .unwrap()
+    }
+
+    async fn picked(tool: &Tool, requested: &str, config: &Config) -> Option<String> {
+        let requested = VersionReq::parse(requested).unwrap();
+
+        find_matching_release(tool, &requested, config)
+            .await
+            .unwrap()
+            .map(|(version, release)| {
+                assert_eq!(release.version(), Some(version.clone()));
+                version.to_string()
+            })
+    }
+
+    #[tokio::test]
+    async fn picks_the_highest_matching_release() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(tmp.path());
+        let tool = local_tool(&tmp.path().join("releases"));
+
+        for (requested, expected) in [
+            ("^0.12", Some("0.12.3")),
+            ("^0.11", Some("0.11.3")),
+            ("=0.11.2", Some("0.11.2")),
+            (">=0.10, <0.12", Some("0.11.3")),
+            ("*", Some("0.12.3")),
+            ("^0.14", None),
+            ("=0.12.1", None),
+        ] {
+            assert_eq!(
+                picked(&tool, requested, &config).await.as_deref(),
+                expected,
+                "{requested}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn prereleases_only_when_asked_for() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(tmp.path());
+        let tool = local_tool(&tmp.path().join("releases"));
+
+        for (requested, expected) in [
+            (">=0.12", Some("0.12.3")),
+            (">=0.13.0-alpha", Some("0.13.0-beta.1")),
+            ("=0.13.0-beta.1", Some("0.13.0-beta.1")),
+            ("^0.13", None),
+        ] {
+            assert_eq!(
+                picked(&tool, requested, &config).await.as_deref(),
+                expected,
+                "{requested}"
+            );
+        }
+    }
+
+    /// Releases kept in memory, for what a dir can't express
+    struct Fixed(Vec<Release>);
+
+    #[async_trait]
+    impl ReleaseSource for Fixed {
+        async fn list_releases(&self) -> anyhow::Result<Vec<Release>> {
+            Ok(self.0.clone())
+        }
+
+        async fn release_by_tag(&self, tag: &str) -> anyhow::Result<Option<Release>> {
+            Ok(self.0.iter().find(|release| release.tag == tag).cloned())
+        }
+    }
+
+    fn release(tag: &str, draft: bool) -> Release {
+        Release {
+            tag: tag.to_string(),
+            draft,
+            prerelease: false,
+            assets: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn drafts_are_never_picked() {
+        let source = Fixed(vec![
+            release("v0.12.0", false),
+            release("v0.12.5", true),
+            release("v0.12.3", false),
+        ]);
+
+        for (requested, expected) in [("^0.12", Some("0.12.3")), ("=0.12.5", None)] {
+            let requested = VersionReq::parse(requested).unwrap();
+
+            let picked = select_release(&source, &requested)
+                .await
+                .unwrap()
+                .map(|(version, _)| version.to_string());
+
+            assert_eq!(picked.as_deref(), expected, "{requested}");
+        }
+    }
+
+    #[test]
+    fn only_exact_requirements_name_a_version() {
+        for (requested, expected) in [
+            ("=0.11.2", Some("0.11.2")),
+            ("=0.13.0-beta.1", Some("0.13.0-beta.1")),
+            ("0.11.2", None),
+            ("^0.11.2", None),
+            ("=0.11", None),
+            (">=0.11.2, <0.12", None),
+        ] {
+            let requested = VersionReq::parse(requested).unwrap();
+
+            assert_eq!(
+                exact_version(&requested).map(|v| v.to_string()).as_deref(),
+                expected,
+                "{requested}"
+            );
+        }
+    }
+}
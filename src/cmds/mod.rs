@@ -1,4 +1,10 @@
+pub mod bundle;
+pub mod cache;
 pub mod check;
+pub mod doctor;
 pub mod install;
+pub mod run;
+pub mod self_;
 pub mod show;
+pub mod uninstall;
 pub mod r#use;
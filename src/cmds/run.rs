@@ -0,0 +1,75 @@
+use std::ffi::OsString;
+
+use anyhow::Context;
+use clap::Parser;
+
+use crate::{ArgsCommon, Config, cmds::install, manifest, updates::Update};
+
+#[derive(Parser)]
+pub struct Args {
+    /// Name of the tool to run
+    pub tool: String,
+
+    /// Arguments forwarded to the tool
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    pub args: Vec<OsString>,
+}
+
+impl ArgsCommon for Args {
+    fn skip_banner(&self) -> bool {
+        // stdout belongs to the tool
+        true
+    }
+}
+
+#[cfg(unix)]
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+pub async fn run(args: &Args, config: &Config) -> anyhow::Result<()> {
+    let manifest = manifest::load_latest_manifest(config, false).await?;
+
+    let tool = manifest
+        .tool_by_name(&args.tool)
+        .ok_or_else(|| anyhow::anyhow!("{} is not part of the toolchain", args.tool))?;
+
+    let bin_path = tool.bin_path(config);
+
+    if !tool.is_global && !bin_path.exists() {
+        eprintln!(
+            "{} is not installed in channel {}, installing it first",
+            tool.name,
+            config.ensure_channel()
+        );
+
+        // stdout belongs to the tool
+        install::report_to_stderr();
+
+        let update = Update {
+            tool: tool.name.clone(),
+            current: None,
+            requested: tool.version.clone(),
+        };
+
+        install::install_updates(&manifest, &[update], install::DEFAULT_JOBS, config).await?;
+    }
+
+    let status = std::process::Command::new(&bin_path)
+        .args(&args.args)
+        .env("TX3_CHANNEL", config.ensure_channel())
+        .status()
+        .with_context(|| format!("running {}", bin_path.display()))?;
+
+    std::process::exit(exit_code(status));
+}
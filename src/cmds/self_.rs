@@ -0,0 +1,139 @@
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use indicatif::MultiProgress;
+use semver::VersionReq;
+
+use crate::{
+    ArgsCommon, Config, bin,
+    cmds::{install, uninstall},
+    manifest::{self, Tool},
+    shims,
+    staging::Transaction,
+    updates,
+};
+
+#[derive(Parser)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Update tx3up to the version required by the channel manifest
+    Update,
+    /// Remove tx3up along with every channel and the shell profile wiring
+    Uninstall {
+        /// List what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+impl ArgsCommon for Args {
+    fn skip_banner(&self) -> bool {
+        matches!(self.command, Command::Uninstall { .. })
+    }
+}
+
+fn ensure_self_update(config: &Config) -> anyhow::Result<()> {
+    if !config.self_update() {
+        anyhow::bail!(
+            "self update is disabled (--no-self-update / TX3_NO_SELF_UPDATE, or tx3up lives outside the tx3 root and CARGO_HOME), update tx3up with the tool that installed it or pass --self-update"
+        );
+    }
+
+    Ok(())
+}
+
+/// Downloads the `tx3up` release required by the manifest, checks it like
+/// any other tool and swaps it for the running executable.
+pub async fn update_self(tool: &Tool, config: &Config) -> anyhow::Result<()> {
+    ensure_self_update(config)?;
+
+    let requested = VersionReq::parse(&tool.version)?;
+
+    println!("\nUpdating {} to {requested} 🔄", tool.name);
+
+    let tx = Transaction::begin(config)?;
+    let multi = MultiProgress::new();
+    let progress = install::tool_progress(&multi, tool);
+
+    let staged = match install::stage_tool(tool, &requested, &tx, config, &progress).await {
+        Ok(staged) => staged,
+        Err(err) => {
+            progress.abandon_with_message(format!("failed: {err}"));
+            tx.rollback()?;
+            return Err(err);
+        }
+    };
+
+    progress.finish_with_message("ready");
+
+    // an instructions installer leaves the update to the user
+    if let Some(staged) = staged {
+        self_replace::self_replace(staged.binary()).context("replacing tx3up executable")?;
+        println!("Successfully updated {}", tool.name);
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+async fn update(config: &Config) -> anyhow::Result<()> {
+    ensure_self_update(config)?;
+
+    let manifest = manifest::load_latest_manifest(config, true).await?;
+
+    let tool = manifest
+        .self_tool()
+        .context("the channel manifest doesn't describe tx3up")?;
+
+    let requested = VersionReq::parse(&tool.version)?;
+
+    if let Some(current) = updates::self_version(tool).await
+        && requested.matches(&current)
+    {
+        println!("{} is up to date 🎉", tool.name);
+        return Ok(());
+    }
+
+    update_self(tool, config).await?;
+
+    // the shims link to a copy of the executable, which is still the old one
+    shims::install_shims(&manifest, config)
+}
+
+fn uninstall(dry_run: bool, config: &Config) -> anyhow::Result<()> {
+    let exe = bin::self_exe()?;
+
+    if !config.self_update() {
+        // the executable may still sit in the root when it's kept
+        uninstall::uninstall_all_except(exe, dry_run, config)?;
+        println!(
+            "kept {}, remove it with the tool that installed it",
+            exe.display()
+        );
+
+        return Ok(());
+    }
+
+    uninstall::uninstall_all(dry_run, config)?;
+
+    if dry_run {
+        println!("would remove: {}", exe.display());
+    } else if exe.exists() {
+        self_replace::self_delete().context("removing tx3up executable")?;
+        println!("removed: {}", exe.display());
+    }
+
+    Ok(())
+}
+
+pub async fn run(args: &Args, config: &Config) -> anyhow::Result<()> {
+    match args.command {
+        Command::Update => update(config).await,
+        Command::Uninstall { dry_run } => uninstall(dry_run, config),
+    }
+}
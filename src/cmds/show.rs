@@ -1,6 +1,10 @@
-use std::process::Command;
+use std::collections::HashMap;
 
-use crate::{ArgsCommon, Config, manifest};
+use crate::{
+    ArgsCommon, Config, bin, manifest,
+    receipts::{self, Receipt},
+    shims, updates,
+};
 
 #[derive(Debug, clap::Parser)]
 pub struct Args {
@@ -13,8 +17,20 @@ impl ArgsCommon for Args {
     }
 }
 
-fn print_tool(tool: &crate::manifest::Tool, config: &Config) -> anyhow::Result<()> {
-    println!("bin path: {}", tool.bin_path(config).display());
+async fn print_tool(
+    tool: &crate::manifest::Tool,
+    is_self: bool,
+    receipts: &HashMap<String, Receipt>,
+    config: &Config,
+) -> anyhow::Result<()> {
+    // tx3up runs from wherever it was installed, not from the channel
+    let bin_path = if is_self {
+        bin::self_exe()?.to_path_buf()
+    } else {
+        tool.bin_path(config)
+    };
+
+    println!("bin path: {}", bin_path.display());
 
     println!(
         "github repo: https://github.com/{}/{}",
@@ -23,30 +39,74 @@ fn print_tool(tool: &crate::manifest::Tool, config: &Config) -> anyhow::Result<(
 
     println!("required version: {}", tool.version);
 
-    let version = Command::new(tool.bin_path(config))
-        .arg(tool.version_cmd())
-        .output()?;
-
-    let version = String::from_utf8(version.stdout)?;
-
-    let version = if version.is_empty() {
-        "not reported\n".to_string()
+    let installed = if is_self {
+        updates::self_version(tool).await
     } else {
-        version
+        updates::find_installed_version(tool, receipts, config).await?
+    };
+
+    let version = match installed {
+        Some(version) => version.to_string(),
+        None => "not reported".to_string(),
     };
 
     println!("installed version: {version}");
 
+    if let Some(receipt) = receipts.get(&tool.name)
+        && let Some(asset) = &receipt.asset
+    {
+        match &receipt.release {
+            Some(release) => println!("installed from: {asset} ({release})"),
+            None => println!("installed from: {asset}"),
+        }
+
+        println!("installed: {}", receipt.installed_ago());
+    }
+
+    // tx3up itself is whatever copy the user ran
+    if !is_self {
+        shims::warn_if_shadowed(tool, config).await;
+    }
+
+    println!();
+
     Ok(())
 }
 
 pub async fn run(_args: &Args, config: &Config) -> anyhow::Result<()> {
     let manifest = manifest::load_latest_manifest(config, false).await?;
 
+    println!(
+        "active channel: {} (from {})",
+        config.ensure_channel(),
+        config.channel_origin()
+    );
+
+    println!(
+        "manifest source: {}",
+        manifest::manifest_source(config).await?
+    );
+
+    println!("target: {}", config.target()?);
+
+    if let Some(project) = config.project() {
+        println!("project toolchain file: {}", project.path.display());
+
+        if let Some(release) = &project.pin.release {
+            println!("pinned toolchain release: {release}");
+        }
+    }
+
+    println!();
+
+    let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+    let receipts = receipts::load_receipts(config)?;
+
     for tool in manifest.tools() {
         println!("{}: {}", tool.name, tool.description);
 
-        let ok = print_tool(tool, config);
+        let is_self = Some(tool.name.as_str()) == self_name;
+        let ok = print_tool(tool, is_self, &receipts, config).await;
 
         if let Err(e) = ok {
             eprintln!("error: {e}");
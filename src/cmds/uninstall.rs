@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::Parser;
+
+use crate::{ArgsCommon, Config, bin, manifest, perm_path, receipts, shims, updates};
+
+#[derive(Parser)]
+#[command(group(
+    clap::ArgGroup::new("scope")
+        .required(true)
+        .args(["tool", "whole_channel", "all"])
+))]
+pub struct Args {
+    /// Name of a single tool to remove from the channel's bin dir
+    pub tool: Option<String>,
+
+    /// Remove the whole channel directory
+    #[arg(long)]
+    pub whole_channel: bool,
+
+    /// Remove the entire root dir, including every channel and the shell
+    /// profile wiring
+    #[arg(long)]
+    pub all: bool,
+
+    /// List what would be deleted without deleting anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl ArgsCommon for Args {
+    fn skip_banner(&self) -> bool {
+        // the banner would create the default channel as a side effect
+        true
+    }
+}
+
+fn remove_path(path: &Path, dry_run: bool) -> anyhow::Result<()> {
+    let is_link = path.is_symlink();
+
+    if !is_link && !path.exists() {
+        return Ok(());
+    }
+
+    if dry_run {
+        println!("would remove: {}", path.display());
+        return Ok(());
+    }
+
+    if !is_link && path.is_dir() {
+        std::fs::remove_dir_all(path).context("removing directory")?;
+    } else {
+        std::fs::remove_file(path).context("removing file")?;
+    }
+
+    println!("removed: {}", path.display());
+
+    Ok(())
+}
+
+fn default_points_to(config: &Config, channel_dir: &Path) -> bool {
+    let Ok(target) = std::fs::read_link(config.fixed_channel_dir()) else {
+        return false;
+    };
+
+    target == channel_dir
+}
+
+async fn uninstall_tool(name: &str, args: &Args, config: &Config) -> anyhow::Result<()> {
+    let channel = config.channel()?;
+
+    if let Some(manifest) = manifest::load_local_manifest(config).await?
+        && let Some(tool) = manifest.tool_by_name(name)
+        && tool.is_global
+    {
+        anyhow::bail!("{name} is a global tool and is not managed by tx3up");
+    }
+
+    let bin_path = config.bin_dir().join(bin::file_name(name));
+
+    if !bin_path.exists() {
+        anyhow::bail!("{name} is not installed in channel {channel}");
+    }
+
+    let mut receipts = receipts::load_receipts(config)?;
+
+    let extras: Vec<_> = receipts
+        .remove(name)
+        .map(|receipt| receipt.extras)
+        .unwrap_or_default()
+        .iter()
+        .map(|extra| config.channel_dir().join(extra))
+        .collect();
+
+    // other channels may still provide the same tools
+    let unused = shims::unused_shims(config, |bin| {
+        bin == bin_path || extras.contains(&bin.to_path_buf())
+    })?;
+
+    remove_path(&bin_path, args.dry_run)?;
+
+    for path in extras.iter().chain(unused.iter()) {
+        remove_path(path, args.dry_run)?;
+    }
+
+    if !args.dry_run {
+        receipts::save_receipts(&receipts, config)?;
+
+        // force the next check to notice the missing tool
+        updates::clear_updates(config).await?;
+    }
+
+    Ok(())
+}
+
+fn uninstall_channel(args: &Args, config: &Config) -> anyhow::Result<()> {
+    let channel = config.channel()?;
+    let channel_dir: PathBuf = config.root_dir().join(&channel);
+
+    if !channel_dir.exists() {
+        anyhow::bail!("channel {channel} is not installed");
+    }
+
+    let unused = shims::unused_shims(config, |bin| bin.starts_with(&channel_dir))?;
+
+    if default_points_to(config, &channel_dir) {
+        remove_path(&config.fixed_channel_dir(), args.dry_run)?;
+    }
+
+    remove_path(&channel_dir, args.dry_run)?;
+
+    for shim in unused.iter() {
+        remove_path(shim, args.dry_run)?;
+    }
+
+    Ok(())
+}
+
+/// Removes the root dir with every channel and the shell profile wiring
+pub fn uninstall_all(dry_run: bool, config: &Config) -> anyhow::Result<()> {
+    let root_dir = config.root_dir();
+
+    perm_path::remove_from_all_profiles(config, dry_run)?;
+
+    remove_path(&config.fixed_channel_dir(), dry_run)?;
+    remove_path(&root_dir, dry_run)?;
+
+    Ok(())
+}
+
+/// Same as `uninstall_all`, but leaves the file at `keep` and the dirs
+/// leading to it in place
+pub fn uninstall_all_except(keep: &Path, dry_run: bool, config: &Config) -> anyhow::Result<()> {
+    let root_dir = config.root_dir();
+
+    perm_path::remove_from_all_profiles(config, dry_run)?;
+
+    remove_path(&config.fixed_channel_dir(), dry_run)?;
+    remove_path_except(&root_dir, keep, dry_run)?;
+
+    Ok(())
+}
+
+fn remove_path_except(path: &Path, keep: &Path, dry_run: bool) -> anyhow::Result<()> {
+    // `keep` is canonical, so compare it with the resolved path
+    let resolved = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    if resolved == keep {
+        return Ok(());
+    }
+
+    if path.is_symlink() || !keep.starts_with(&resolved) {
+        return remove_path(path, dry_run);
+    }
+
+    for entry in std::fs::read_dir(path).context("reading directory")? {
+        remove_path_except(&entry?.path(), keep, dry_run)?;
+    }
+
+    Ok(())
+}
+
+pub async fn run(args: &Args, config: &Config) -> anyhow::Result<()> {
+    if let Some(tool) = &args.tool {
+        uninstall_tool(tool, args, config).await?;
+    } else if args.whole_channel {
+        uninstall_channel(args, config)?;
+    } else if args.all {
+        uninstall_all(args.dry_run, config)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,454 @@
+use std::{
+    fs::{File, TryLockError},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use indicatif::ProgressBar;
+use reqwest::{Client, Response, StatusCode, Url, header};
+use sha2::{Digest, Sha256};
+
+use crate::Config;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadError {
+    #[error("request to {url} failed")]
+    Request {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    #[error("server responded with {status} for {url}")]
+    Status { url: String, status: StatusCode },
+
+    #[error("no data received from {url} for {timeout:?}")]
+    Timeout { url: String, timeout: Duration },
+
+    #[error("accessing {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("giving up on {url} after {attempts} attempts")]
+    Exhausted {
+        url: String,
+        attempts: u32,
+        #[source]
+        last: Box<DownloadError>,
+    },
+}
+
+impl DownloadError {
+    /// Whether the file doesn't exist at all, as opposed to failing to fetch
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            DownloadError::Status { status, .. } => *status == StatusCode::NOT_FOUND,
+            DownloadError::Io { source, .. } => source.kind() == std::io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+
+    /// Client errors won't go away by asking again, except for the ones that
+    /// explicitly tell us to.
+    fn is_retryable(&self) -> bool {
+        match self {
+            DownloadError::Status { status, .. } => {
+                !status.is_client_error()
+                    || *status == StatusCode::REQUEST_TIMEOUT
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            DownloadError::Io { .. } => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// How many times a failed attempt is retried
+    pub retries: u32,
+
+    /// Max time to wait for the server to send the next chunk of data
+    pub timeout: Duration,
+}
+
+impl DownloadOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            retries: config.download_retries(),
+            timeout: config.download_timeout(),
+        }
+    }
+}
+
+/// Where a download of `url` is kept until it completes. It lives outside
+/// the stage dirs so that an interrupted download can be resumed by a later
+/// run.
+pub fn partial_path(url: &str, config: &Config) -> PathBuf {
+    let key = hex::encode(Sha256::digest(url.as_bytes()));
+
+    config
+        .partial_downloads_dir()
+        .join(format!("{key}.partial"))
+}
+
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF)
+}
+
+/// Runs `attempt` until it succeeds, fails with an error that won't go away
+/// or runs out of retries, waiting longer between each try.
+async fn retry<T, Fut>(
+    url: &str,
+    options: &DownloadOptions,
+    mut attempt: impl FnMut() -> Fut,
+    on_retry: impl Fn(Duration, &DownloadError),
+) -> Result<T, DownloadError>
+where
+    Fut: Future<Output = Result<T, DownloadError>>,
+{
+    let mut attempts = 0;
+
+    loop {
+        let error = match attempt().await {
+            Ok(x) => return Ok(x),
+            Err(error) => error,
+        };
+
+        if !error.is_retryable() {
+            return Err(error);
+        }
+
+        if attempts >= options.retries {
+            return Err(DownloadError::Exhausted {
+                url: url.to_string(),
+                attempts: attempts + 1,
+                last: Box::new(error),
+            });
+        }
+
+        let wait = backoff(attempts);
+        on_retry(wait, &error);
+        tokio::time::sleep(wait).await;
+
+        attempts += 1;
+    }
+}
+
+async fn with_timeout<T>(
+    url: &str,
+    timeout: Duration,
+    fut: impl Future<Output = Result<T, reqwest::Error>>,
+) -> Result<T, DownloadError> {
+    match tokio::time::timeout(timeout, fut).await {
+        Ok(Ok(x)) => Ok(x),
+        Ok(Err(source)) => Err(DownloadError::Request {
+            url: url.to_string(),
+            source,
+        }),
+        Err(_) => Err(DownloadError::Timeout {
+            url: url.to_string(),
+            timeout,
+        }),
+    }
+}
+
+async fn send(
+    client: &Client,
+    url: &str,
+    resume_from: u64,
+    options: &DownloadOptions,
+) -> Result<Response, DownloadError> {
+    let mut request = client.get(url);
+
+    if resume_from > 0 {
+        request = request.header(header::RANGE, format!("bytes={resume_from}-"));
+    }
+
+    let response = with_timeout(url, options.timeout, request.send()).await?;
+
+    let status = response.status();
+
+    if !status.is_success() {
+        return Err(DownloadError::Status {
+            url: url.to_string(),
+            status,
+        });
+    }
+
+    Ok(response)
+}
+
+/// Path of a `file://` url, whose content is read straight from disk
+fn local_path(url: &str) -> Option<PathBuf> {
+    let url = Url::parse(url).ok().filter(|url| url.scheme() == "file")?;
+
+    url.to_file_path().ok()
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> DownloadError + '_ {
+    |source| DownloadError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+async fn download_attempt(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    options: &DownloadOptions,
+    progress: &ProgressBar,
+) -> Result<(), DownloadError> {
+    let existing = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
+    let mut response = match send(client, url, existing, options).await {
+        // whatever we have on disk doesn't fit the remote file anymore
+        Err(DownloadError::Status { status, .. })
+            if existing > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE =>
+        {
+            send(client, url, 0, options).await?
+        }
+        x => x?,
+    };
+
+    // appending a range that doesn't start where our data ends would corrupt
+    // the file, so start over instead
+    if response.status() == StatusCode::PARTIAL_CONTENT
+        && content_range_start(&response) != Some(existing)
+    {
+        response = send(client, url, 0, options).await?;
+    }
+
+    // the server may ignore the range header and send the whole file again
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+    let offset = if resumed { existing } else { 0 };
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(path)
+        .map_err(io_error(path))?;
+
+    progress.set_length(offset + response.content_length().unwrap_or(0));
+    progress.set_position(offset);
+
+    while let Some(chunk) = with_timeout(url, options.timeout, response.chunk()).await? {
+        file.write_all(&chunk).map_err(io_error(path))?;
+        progress.inc(chunk.len() as u64);
+    }
+
+    file.flush().map_err(io_error(path))?;
+
+    Ok(())
+}
+
+/// Start of the range a partial response carries, from a `Content-Range`
+/// header such as `bytes 100-199/200`
+fn content_range_start(response: &Response) -> Option<u64> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+
+    parse_range_start(value)
+}
+
+fn parse_range_start(value: &str) -> Option<u64> {
+    let (start, _) = value.strip_prefix("bytes ")?.split_once('-')?;
+
+    start.trim().parse().ok()
+}
+
+/// Locks the partial download at `path`, so that two runs installing the
+/// same asset don't write into the same file. The lock is released when the
+/// returned file is dropped.
+pub fn lock_partial(path: &Path, progress: &ProgressBar) -> Result<File, DownloadError> {
+    let lock_path = path.with_extension("lock");
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&lock_path)
+        .map_err(io_error(&lock_path))?;
+
+    match file.try_lock() {
+        Ok(()) => (),
+        Err(TryLockError::WouldBlock) => {
+            progress.set_message("waiting for another download of the same asset");
+            file.lock().map_err(io_error(&lock_path))?;
+        }
+        Err(TryLockError::Error(source)) => return Err(io_error(&lock_path)(source)),
+    }
+
+    Ok(file)
+}
+
+/// Downloads `url` into `path`, retrying with exponential backoff and
+/// resuming from the partially downloaded data when the server supports it.
+pub async fn download_file(
+    url: &str,
+    path: &Path,
+    options: &DownloadOptions,
+    progress: &ProgressBar,
+) -> Result<(), DownloadError> {
+    if let Some(local) = local_path(url) {
+        let copied = std::fs::copy(&local, path).map_err(io_error(&local))?;
+
+        progress.set_length(copied);
+        progress.set_position(copied);
+
+        return Ok(());
+    }
+
+    let client = Client::new();
+
+    retry(
+        url,
+        options,
+        || download_attempt(&client, url, path, options, progress),
+        |wait, error| progress.set_message(format!("retrying in {wait:?}: {error}")),
+    )
+    .await
+}
+
+/// Fetches a small text asset, such as a manifest or a checksum file, with
+/// the same retry policy as file downloads.
+pub async fn fetch_text(url: &str, options: &DownloadOptions) -> Result<String, DownloadError> {
+    if let Some(local) = local_path(url) {
+        return std::fs::read_to_string(&local).map_err(io_error(&local));
+    }
+
+    let client = Client::new();
+
+    retry(
+        url,
+        options,
+        || async {
+            let response = send(&client, url, 0, options).await?;
+            with_timeout(url, options.timeout, response.text()).await
+        },
+        |_, _| (),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    const BODY: &str = "0123456789";
+
+    #[test]
+    fn parses_the_start_of_content_ranges() {
+        let cases = [
+            ("bytes 100-199/200", Some(100)),
+            ("bytes 0-9/*", Some(0)),
+            ("bytes */200", None),
+            ("items 100-199/200", None),
+            ("bytes -5-9/10", None),
+            ("", None),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(parse_range_start(value), expected, "{value}");
+        }
+    }
+
+    /// Serves `BODY`, answering range requests with a partial response that
+    /// starts at `range_start` no matter which range was asked for
+    async fn serve(range_start: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 4096];
+                let read = socket.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]).to_lowercase();
+
+                let response = if request.contains("range: bytes=") {
+                    let body = &BODY[range_start..];
+
+                    format!(
+                        "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes {range_start}-{}/{}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        BODY.len() - 1,
+                        BODY.len(),
+                        body.len()
+                    )
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{BODY}",
+                        BODY.len()
+                    )
+                };
+
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        format!("http://{addr}/trix.tar.gz")
+    }
+
+    async fn resume(range_start: usize) -> String {
+        let url = serve(range_start).await;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("trix.partial");
+        std::fs::write(&path, &BODY[..4]).unwrap();
+
+        let options = DownloadOptions {
+            retries: 0,
+            timeout: Duration::from_secs(5),
+        };
+
+        download_file(&url, &path, &options, &ProgressBar::hidden())
+            .await
+            .unwrap();
+
+        std::fs::read_to_string(&path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resumes_where_the_partial_download_ends() {
+        assert_eq!(resume(4).await, BODY);
+    }
+
+    #[tokio::test]
+    async fn starts_over_when_the_range_does_not_line_up() {
+        assert_eq!(resume(2).await, BODY);
+        assert_eq!(resume(0).await, BODY);
+    }
+
+    #[test]
+    fn partial_locks_are_exclusive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("trix.partial");
+
+        let held = lock_partial(&path, &ProgressBar::hidden()).unwrap();
+
+        let other = File::open(path.with_extension("lock")).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+
+        drop(held);
+        assert!(other.try_lock().is_ok());
+    }
+}
@@ -1,13 +1,28 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Once, OnceLock},
+};
 
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 
+mod archive;
 mod banner;
 mod bin;
+mod cache;
+mod checksum;
 mod cmds;
+mod download;
 mod manifest;
+mod overrides;
 mod perm_path;
+mod project;
+mod receipts;
+mod shims;
+mod signature;
+mod sources;
+mod staging;
+mod target;
 mod updates;
 
 #[derive(Parser)]
@@ -22,6 +37,97 @@ struct Cli {
     #[arg(global = true, long, env = "GITHUB_TOKEN", hide = true)]
     github_token: Option<String>,
 
+    /// Extra minisign public key trusted to sign channel manifests
+    #[arg(
+        global = true,
+        long = "trust-key",
+        env = "TX3_TRUSTED_KEYS",
+        value_delimiter = ','
+    )]
+    trusted_keys: Vec<String>,
+
+    /// Seconds to wait for a download to make progress before retrying
+    #[arg(
+        global = true,
+        long,
+        env = "TX3_DOWNLOAD_TIMEOUT",
+        default_value_t = 30
+    )]
+    download_timeout: u64,
+
+    /// Number of times a failed download is retried
+    #[arg(global = true, long, env = "TX3_DOWNLOAD_RETRIES", default_value_t = 5)]
+    download_retries: u32,
+
+    /// Where to look for releases: github, index:<url> or dir:<path>
+    #[arg(
+        global = true,
+        long,
+        env = "TX3_RELEASE_SOURCE",
+        default_value = "github"
+    )]
+    release_source: sources::SourceRoot,
+
+    /// Take the channel manifest from the releases of this repo (owner/name)
+    #[arg(global = true, long, conflicts_with = "manifest_url")]
+    manifest_repo: Option<String>,
+
+    /// Take the channel manifest from this url or local file, defaults to
+    /// TX3_MANIFEST_URL
+    #[arg(global = true, long)]
+    manifest_url: Option<String>,
+
+    /// Size limit of the download cache in MiB, 0 for no limit
+    #[arg(
+        global = true,
+        long,
+        env = "TX3_CACHE_SIZE_LIMIT",
+        default_value_t = 1024
+    )]
+    cache_size_limit: u64,
+
+    /// Target triple to install binaries for (default: detected)
+    #[arg(global = true, long, env = "TX3_TARGET")]
+    target: Option<String>,
+
+    /// Leave tx3up itself alone, e.g. when a package manager installed it
+    #[arg(
+        global = true,
+        long,
+        env = "TX3_NO_SELF_UPDATE",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    no_self_update: bool,
+
+    /// Replace tx3up even when it lives outside the tx3 root and CARGO_HOME,
+    /// `--no-self-update` still wins
+    #[arg(
+        global = true,
+        long,
+        env = "TX3_SELF_UPDATE",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    self_update: bool,
+
+    /// Accept channel manifests without a signature, as long as no extra key
+    /// is trusted and the channel's previous manifest wasn't signed
+    #[arg(
+        global = true,
+        long,
+        env = "TX3_ALLOW_UNSIGNED",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    allow_unsigned: bool,
+
+    /// Never touch the network, work from the cached manifest and downloads
+    #[arg(
+        global = true,
+        long,
+        env = "TX3_OFFLINE",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    offline: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -33,12 +139,23 @@ enum Commands {
     /// Check for updates
     Check(cmds::check::Args),
     /// Uninstall the tx3 toolchain
-    Uninstall,
+    Uninstall(cmds::uninstall::Args),
     /// Set the default channel
     #[command(alias("default"))]
     Use(cmds::r#use::Args),
     /// Show the version of the tx3 toolchain
     Show(cmds::show::Args),
+    /// Run a tool from a specific channel
+    Run(cmds::run::Args),
+    /// Manage the download cache
+    Cache(cmds::cache::Args),
+    /// Export or import the toolchain for machines without internet
+    Bundle(cmds::bundle::Args),
+    /// Update or uninstall tx3up itself
+    #[command(name = "self")]
+    Self_(cmds::self_::Args),
+    /// Diagnose problems with the installed toolchain
+    Doctor(cmds::doctor::Args),
 }
 
 pub trait ArgsCommon {
@@ -52,22 +169,176 @@ impl Commands {
             Commands::Check(x) => x.skip_banner(),
             Commands::Use(x) => x.skip_banner(),
             Commands::Show(x) => x.skip_banner(),
-            Commands::Uninstall => true,
+            Commands::Run(x) => x.skip_banner(),
+            Commands::Uninstall(x) => x.skip_banner(),
+            Commands::Cache(x) => x.skip_banner(),
+            Commands::Bundle(x) => x.skip_banner(),
+            Commands::Self_(x) => x.skip_banner(),
+            Commands::Doctor(x) => x.skip_banner(),
         }
     }
 }
 
+/// Manifest source as given on the command line or environment
+#[derive(Clone)]
+enum ManifestSourceArg {
+    Repo(String),
+    Url(String),
+}
+
+#[derive(Clone)]
 pub struct Config {
     root_dir: Option<PathBuf>,
     channel: Option<String>,
     github_token: Option<String>,
+    trusted_keys: Vec<String>,
+    download_timeout: u64,
+    download_retries: u32,
+    /// Discovered on first use, so that a broken pin file only gets in the
+    /// way of the commands that need it
+    project: OnceLock<Result<Option<project::ProjectToolchain>, String>>,
+    release_source: sources::SourceRoot,
+    manifest_source: Option<ManifestSourceArg>,
+    offline: bool,
+    cache_size_limit: u64,
+    target: Option<String>,
+    no_self_update: bool,
+    self_update: bool,
+    allow_unsigned: bool,
 }
 
 impl Config {
+    fn from_cli(cli: &Cli) -> Result<Self> {
+        // flags win over the environment, so `--manifest-repo` still works
+        // with TX3_MANIFEST_URL set; both flags at once are rejected by clap
+        let manifest_source = match (&cli.manifest_repo, &cli.manifest_url) {
+            (Some(repo), _) => Some(ManifestSourceArg::Repo(repo.clone())),
+            (None, Some(url)) => Some(ManifestSourceArg::Url(url.clone())),
+            (None, None) => std::env::var("TX3_MANIFEST_URL")
+                .ok()
+                .filter(|url| !url.is_empty())
+                .map(ManifestSourceArg::Url),
+        };
+
+        Ok(Self {
+            root_dir: cli.root_dir.clone(),
+            channel: cli.channel.clone(),
+            github_token: cli.github_token.clone(),
+            trusted_keys: cli.trusted_keys.clone(),
+            download_timeout: cli.download_timeout,
+            download_retries: cli.download_retries,
+            project: OnceLock::new(),
+            release_source: cli.release_source.clone(),
+            manifest_source,
+            offline: cli.offline,
+            cache_size_limit: cli.cache_size_limit,
+            target: cli.target.clone(),
+            no_self_update: cli.no_self_update,
+            self_update: cli.self_update,
+            allow_unsigned: cli.allow_unsigned,
+        })
+    }
+
     pub fn github_token(&self) -> Option<&str> {
         self.github_token.as_deref().filter(|t| !t.is_empty())
     }
 
+    pub fn source_root(&self) -> &sources::SourceRoot {
+        &self.release_source
+    }
+
+    /// Manifest source given explicitly for this invocation. It's resolved
+    /// on use, so that a missing manifest file only fails the commands that
+    /// read it.
+    pub fn manifest_source(&self) -> Result<Option<manifest::ManifestSource>> {
+        let source = match &self.manifest_source {
+            Some(ManifestSourceArg::Repo(repo)) => manifest::ManifestSource::from_repo(repo)?,
+            Some(ManifestSourceArg::Url(url)) => manifest::ManifestSource::from_url(url)?,
+            None => return Ok(None),
+        };
+
+        Ok(Some(source))
+    }
+
+    /// Target triple of the release assets to install
+    pub fn target(&self) -> Result<String> {
+        self.target
+            .clone()
+            .or_else(target::detect)
+            .context("unsupported platform, set the target triple with --target / TX3_TARGET")
+    }
+
+    /// Whether tx3up may replace its own executable. Unless asked to, a copy
+    /// outside the tx3 root and CARGO_HOME is left to whatever package
+    /// manager put it there.
+    pub fn self_update(&self) -> bool {
+        if self.no_self_update {
+            return false;
+        }
+
+        self.self_update || bin::self_exe().is_ok_and(|exe| self.owns_exe(exe))
+    }
+
+    /// Whether the executable at `exe` was installed by tx3up or its
+    /// installer, which puts it in CARGO_HOME
+    fn owns_exe(&self, exe: &Path) -> bool {
+        let cargo_home = std::env::var_os("CARGO_HOME")
+            .filter(|home| !home.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")));
+
+        [Some(self.root_dir()), cargo_home]
+            .into_iter()
+            .flatten()
+            .filter_map(|dir| dir.canonicalize().ok())
+            .any(|dir| exe.starts_with(dir))
+    }
+
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Same settings, without network access
+    pub fn offline_copy(&self) -> Self {
+        Self {
+            offline: true,
+            ..self.clone()
+        }
+    }
+
+    /// Default settings for unit tests, on the `stable` channel of a root
+    /// at `root_dir`
+    #[cfg(test)]
+    pub fn for_tests(root_dir: &Path) -> Self {
+        let cli = Cli::try_parse_from([
+            std::ffi::OsStr::new("tx3up"),
+            "--root-dir".as_ref(),
+            root_dir.as_os_str(),
+            "--channel".as_ref(),
+            "stable".as_ref(),
+            "show".as_ref(),
+        ])
+        .unwrap();
+
+        Self::from_cli(&cli).unwrap()
+    }
+
+    pub fn extra_trusted_keys(&self) -> &[String] {
+        &self.trusted_keys
+    }
+
+    pub fn allow_unsigned(&self) -> bool {
+        self.allow_unsigned
+    }
+
+    pub fn download_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.download_timeout)
+    }
+
+    pub fn download_retries(&self) -> u32 {
+        self.download_retries
+    }
+
     pub fn default_root_dir() -> Result<PathBuf> {
         let mut path = if cfg!(target_os = "windows") {
             dirs::data_local_dir()
@@ -87,6 +358,31 @@ impl Config {
             .unwrap_or_else(|| Self::default_root_dir().unwrap())
     }
 
+    /// Shared dir holding the shims that dispatch to the active channel
+    pub fn shims_dir(&self) -> PathBuf {
+        self.root_dir().join("bin")
+    }
+
+    /// Release assets kept around for offline installs, shared by channels
+    pub fn download_cache_dir(&self) -> PathBuf {
+        self.root_dir().join("cache").join("downloads")
+    }
+
+    /// Downloads that haven't completed yet, kept so that they can be resumed
+    pub fn partial_downloads_dir(&self) -> PathBuf {
+        self.root_dir().join("cache").join("partial")
+    }
+
+    /// Size limit of the download cache in bytes, if any
+    pub fn cache_size_limit(&self) -> Option<u64> {
+        (self.cache_size_limit > 0).then(|| self.cache_size_limit.saturating_mul(1024 * 1024))
+    }
+
+    /// Scratch dir used while creating or unpacking a bundle
+    pub fn bundle_work_dir(&self) -> PathBuf {
+        self.root_dir().join(".bundle")
+    }
+
     pub fn fixed_channel_dir(&self) -> PathBuf {
         self.root_dir().join("default")
     }
@@ -118,11 +414,63 @@ impl Config {
 
         std::fs::create_dir_all(&channel_dir)?;
 
+        #[cfg(unix)]
         std::os::unix::fs::symlink(&channel_dir, &fixed_channel_dir)?;
 
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_dir(&channel_dir, &fixed_channel_dir)?;
+
         Ok(())
     }
 
+    fn discover_project(&self) -> &Result<Option<project::ProjectToolchain>, String> {
+        self.project.get_or_init(|| {
+            let cwd = std::env::current_dir().map_err(|err| err.to_string())?;
+            project::discover(&cwd).map_err(|err| format!("{err:#}"))
+        })
+    }
+
+    /// The toolchain pin of the project the command runs in, if any. A pin
+    /// file that can't be read is ignored with a warning.
+    pub fn project(&self) -> Option<&project::ProjectToolchain> {
+        match self.discover_project() {
+            Ok(project) => project.as_ref(),
+            Err(err) => {
+                static WARNED: Once = Once::new();
+                WARNED.call_once(|| eprintln!("warning: ignoring project toolchain pin: {err}"));
+                None
+            }
+        }
+    }
+
+    /// Fails if the project the command runs in has a pin file that can't be
+    /// read, for commands that shouldn't go on without it
+    pub fn ensure_project(&self) -> Result<()> {
+        match self.discover_project() {
+            Ok(_) => Ok(()),
+            Err(err) => Err(anyhow::anyhow!("{err}")),
+        }
+    }
+
+    pub fn pinned_release(&self) -> Option<&str> {
+        self.project()?.pin.release.as_deref()
+    }
+
+    /// Describes where the active channel comes from
+    pub fn channel_origin(&self) -> String {
+        if self.channel.is_some() {
+            return "--channel / TX3_CHANNEL".to_string();
+        }
+
+        if let Some(project) = self.project()
+            && project.pin.channel.is_some()
+        {
+            return project.path.display().to_string();
+        }
+
+        format!("{} symlink", self.fixed_channel_dir().display())
+    }
+
     pub fn channel(&self) -> anyhow::Result<String> {
         let explicit = self.channel.clone();
 
@@ -130,6 +478,10 @@ impl Config {
             return Ok(explicit);
         }
 
+        if let Some(pinned) = self.project().and_then(|p| p.pin.channel.clone()) {
+            return Ok(pinned);
+        }
+
         if let Some(default) = self.fixed_channel()? {
             return Ok(default);
         }
@@ -156,10 +508,41 @@ impl Config {
         self.channel_dir().join("bin")
     }
 
+    pub fn staging_dir(&self) -> PathBuf {
+        self.channel_dir().join(".staging")
+    }
+
+    pub fn backup_dir(&self) -> PathBuf {
+        self.channel_dir().join(".backup")
+    }
+
     pub fn manifest_file(&self) -> PathBuf {
         self.channel_dir().join("manifest.json")
     }
 
+    pub fn manifest_signature_file(&self) -> PathBuf {
+        self.channel_dir().join("manifest.json.minisig")
+    }
+
+    /// Records the toolchain release the cached manifest was taken from
+    pub fn manifest_release_file(&self) -> PathBuf {
+        self.channel_dir().join("manifest.release")
+    }
+
+    /// Records where the cached manifest was downloaded from
+    pub fn manifest_source_file(&self) -> PathBuf {
+        self.channel_dir().join("manifest.source.json")
+    }
+
+    pub fn overrides_file(&self) -> PathBuf {
+        self.channel_dir().join("overrides.json")
+    }
+
+    /// Records what each install left in the channel
+    pub fn receipts_file(&self) -> PathBuf {
+        self.channel_dir().join("receipts.json")
+    }
+
     pub fn updates_file(&self) -> PathBuf {
         self.channel_dir().join("updates.json")
     }
@@ -167,15 +550,21 @@ impl Config {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    if let Some(tool) = shims::proxied_tool() {
+        // shims take every setting from the environment, args belong to the tool
+        let shim_config = Config::from_cli(&Cli::parse_from(["tx3up"]))?;
+
+        return shims::run_proxy(&tool, &shim_config);
+    }
+
     let cli = Cli::parse();
 
-    let config = Config {
-        root_dir: cli.root_dir,
-        channel: cli.channel,
-        github_token: cli.github_token,
-    };
+    // resolve it before a self update gets the chance to replace it
+    let _ = bin::self_exe();
+
+    let config = Config::from_cli(&cli)?;
 
-    let skip_banner = cli.command.as_ref().map_or(false, |c| c.skip_banner());
+    let skip_banner = cli.command.as_ref().is_some_and(|c| c.skip_banner());
 
     if !skip_banner {
         banner::print_banner(&config);
@@ -187,7 +576,12 @@ async fn main() -> anyhow::Result<()> {
             Commands::Check(args) => cmds::check::run(&args, &config).await?,
             Commands::Use(args) => cmds::r#use::run(&args, &config).await?,
             Commands::Show(args) => cmds::show::run(&args, &config).await?,
-            Commands::Uninstall => todo!(),
+            Commands::Run(args) => cmds::run::run(&args, &config).await?,
+            Commands::Uninstall(args) => cmds::uninstall::run(&args, &config).await?,
+            Commands::Cache(args) => cmds::cache::run(&args, &config).await?,
+            Commands::Bundle(args) => cmds::bundle::run(&args, &config).await?,
+            Commands::Self_(args) => cmds::self_::run(&args, &config).await?,
+            Commands::Doctor(args) => cmds::doctor::run(&args, &config).await?,
         }
     } else {
         cmds::install::run(&cmds::install::Args::default(), &config).await?;
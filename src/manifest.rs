@@ -1,34 +1,82 @@
 use anyhow::Context;
-use octocrab::{Octocrab, models::repos::Release, repos::RepoHandler};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     path::PathBuf,
     time::{Duration, SystemTime},
 };
 use tokio::fs;
 
-use crate::Config;
+use crate::{
+    Config,
+    archive::ArchiveFormat,
+    bin,
+    download::{self, DownloadOptions},
+    overrides, signature,
+    sources::{self, Release, ReleaseSource, SourceConfig},
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum Installer {
+    #[default]
+    GithubRelease,
+    Instructions,
+}
+
+/// A file of the release archive to install along with the tool binary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraEntry {
+    /// Glob matched against the path of each archive entry, or against its
+    /// file name when the pattern has no `/`
+    pub pattern: String,
+
+    /// Dir relative to the channel dir, e.g. `bin`, `share/man/man1` or
+    /// `share/completions`
+    pub dest: String,
+}
+
+impl ExtraEntry {
+    /// Fails if the destination could escape the channel dir
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let dest = std::path::Path::new(&self.dest);
+
+        let escapes = dest.is_absolute()
+            || dest
+                .components()
+                .any(|c| !matches!(c, std::path::Component::Normal(_)));
 
-pub fn build_octocrab(config: &Config) -> anyhow::Result<Octocrab> {
-    let mut builder = Octocrab::builder();
+        if escapes {
+            anyhow::bail!("extra destination {} must be a relative path", self.dest);
+        }
 
-    if let Some(token) = config.github_token() {
-        builder = builder.personal_token(token.to_string());
+        Ok(())
     }
+}
+
+/// Regex locating a version in the output of a version command, compiled
+/// when the manifest is parsed so that a broken one fails the load
+#[derive(Debug, Clone)]
+pub struct VersionRegex(regex::Regex);
 
-    builder.build().context("building octocrab client")
+impl VersionRegex {
+    pub fn regex(&self) -> &regex::Regex {
+        &self.0
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Installer {
-    GithubRelease,
-    Instructions,
+impl Serialize for VersionRegex {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
 }
 
-impl Default for Installer {
-    fn default() -> Self {
-        Self::GithubRelease
+impl<'de> Deserialize<'de> for VersionRegex {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+
+        regex::Regex::new(&pattern)
+            .map(Self)
+            .map_err(|err| serde::de::Error::custom(format!("invalid version_regex: {err}")))
     }
 }
 
@@ -48,6 +96,42 @@ pub struct Tool {
 
     #[serde(default)]
     pub installer: Installer,
+
+    /// Expected SHA-256 digest of the release asset, keyed by target triple
+    #[serde(default)]
+    pub digests: HashMap<String, String>,
+
+    /// Name of the release asset per target triple, with `{name}`,
+    /// `{version}` and `{target}` placeholders. Targets not listed fall back
+    /// to the first asset containing `<name>-<target>`
+    #[serde(default)]
+    pub assets: HashMap<String, String>,
+
+    /// Format of the release asset, detected from its content if not set
+    #[serde(default)]
+    pub format: Option<ArchiveFormat>,
+
+    /// Files of the release archive to install besides the binary
+    #[serde(default)]
+    pub extras: Vec<ExtraEntry>,
+
+    /// Arguments that make the tool print its version, `--version` by default
+    #[serde(default)]
+    pub version_args: Option<Vec<String>>,
+
+    /// Regex locating the version in the output of the version command, the
+    /// first capture group is used if there's one
+    #[serde(default)]
+    pub version_regex: Option<VersionRegex>,
+
+    /// Dotted path of the version in a JSON output, e.g. `package.version`
+    #[serde(default)]
+    pub version_json_path: Option<String>,
+
+    /// Where to look for releases, defaults to the global release source for
+    /// `repo_owner/repo_name`
+    #[serde(default)]
+    pub source: Option<SourceConfig>,
 }
 
 impl Tool {
@@ -55,12 +139,42 @@ impl Tool {
         if self.is_global {
             self.name.clone().into()
         } else {
-            config.bin_dir().join(self.name.clone())
+            config.bin_dir().join(self.bin_file_name())
         }
     }
 
-    pub fn version_cmd(&self) -> String {
-        "--version".to_string()
+    pub fn bin_file_name(&self) -> String {
+        bin::file_name(&self.name)
+    }
+
+    /// Expands the asset name template for `target`, if there's one
+    pub fn asset_name(&self, target: &str, version: &semver::Version) -> Option<String> {
+        let template = self.assets.get(target)?;
+
+        Some(
+            template
+                .replace("{name}", &self.name)
+                .replace("{version}", &version.to_string())
+                .replace("{target}", target),
+        )
+    }
+
+    pub fn source(&self, config: &Config) -> SourceConfig {
+        self.source.clone().unwrap_or_else(|| {
+            config
+                .source_root()
+                .for_repo(&self.repo_owner, &self.repo_name)
+        })
+    }
+
+    pub fn pinned_digest(&self, target: &str) -> Option<&str> {
+        self.digests.get(target).map(String::as_str)
+    }
+
+    pub fn version_args(&self) -> Vec<String> {
+        self.version_args
+            .clone()
+            .unwrap_or_else(|| vec!["--version".to_string()])
     }
 }
 
@@ -72,6 +186,10 @@ pub struct Manifest {
 }
 
 impl Manifest {
+    pub fn self_tool(&self) -> Option<&Tool> {
+        self.self_.as_ref()
+    }
+
     pub fn tools(&self) -> impl Iterator<Item = &Tool> {
         self.self_.iter().chain(self.tools.iter())
     }
@@ -79,58 +197,248 @@ impl Manifest {
     pub fn tool_by_name(&self, name: &str) -> Option<&Tool> {
         self.tools().find(|tool| tool.name == name)
     }
-}
 
-async fn fetch_manifest_content(url: &str) -> anyhow::Result<String> {
-    let client = Client::new();
+    /// Replaces the version requirement of the given tools, returning the
+    /// names of the ones the manifest doesn't have (anymore)
+    pub fn apply_overrides(&mut self, overrides: &HashMap<String, String>) -> Vec<String> {
+        let mut unknown = vec![];
+
+        for (name, version) in overrides {
+            let tool = self
+                .self_
+                .iter_mut()
+                .chain(self.tools.iter_mut())
+                .find(|tool| &tool.name == name);
+
+            match tool {
+                Some(tool) => tool.version = version.clone(),
+                None => unknown.push(name.clone()),
+            }
+        }
 
-    let response = client.get(url).send().await.context("fetching manifest")?;
+        unknown
+    }
+}
 
-    let data = response.text().await.context("reading manifest response")?;
+async fn fetch_manifest_content(url: &str, config: &Config) -> anyhow::Result<String> {
+    let options = DownloadOptions::from_config(config);
+
+    let data = download::fetch_text(url, &options).await?;
 
     Ok(data)
 }
 
+/// Fetches the detached signature of a manifest, `None` if there's none
+async fn fetch_manifest_signature(url: &str, config: &Config) -> anyhow::Result<Option<String>> {
+    let options = DownloadOptions::from_config(config);
+
+    match download::fetch_text(url, &options).await {
+        Ok(signature) => Ok(Some(signature)),
+        Err(err) if err.is_not_found() => Ok(None),
+        Err(err) => Err(err).context("fetching manifest signature"),
+    }
+}
+
 async fn define_release(
-    repo: &RepoHandler<'_>,
+    source: &dyn ReleaseSource,
     explicit_tag: Option<&str>,
 ) -> anyhow::Result<Release> {
     if let Some(explicit) = explicit_tag {
-        return repo
-            .releases()
-            .get_by_tag(explicit)
+        source
+            .release_by_tag(explicit)
             .await
-            .context("fetching release");
+            .context("fetching release")?
+            .ok_or_else(|| anyhow::anyhow!("No toolchain release found for tag {explicit}"))
     } else {
-        repo.releases()
-            .get_latest()
+        source
+            .latest_release()
             .await
-            .context("fetching latest release")
+            .context("fetching latest release")?
+            .ok_or_else(|| anyhow::anyhow!("No toolchain release found"))
     }
 }
 
-pub async fn download_remote_manifest(
-    config: &Config,
-    explicit_tag: Option<&str>,
-) -> anyhow::Result<()> {
-    let octocrab = build_octocrab(config)?;
+/// Where the manifest of a channel is published
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ManifestSource {
+    /// Releases of a toolchain repo, holding a `manifest-<channel>.json` asset
+    Repo { owner: String, repo: String },
+    /// A manifest file served as is, signed by `<url>.minisig`
+    Url { url: String },
+}
 
-    let repo = octocrab.repos("tx3-lang", "toolchain");
+impl Default for ManifestSource {
+    fn default() -> Self {
+        Self::Repo {
+            owner: "tx3-lang".to_string(),
+            repo: "toolchain".to_string(),
+        }
+    }
+}
+
+impl ManifestSource {
+    /// Parses an `owner/name` repo reference
+    pub fn from_repo(value: &str) -> anyhow::Result<Self> {
+        let (owner, repo) = value
+            .split_once('/')
+            .filter(|(owner, repo)| !owner.is_empty() && !repo.is_empty() && !repo.contains('/'))
+            .ok_or_else(|| {
+                anyhow::anyhow!("manifest repo must look like owner/name, got {value}")
+            })?;
+
+        Ok(Self::Repo {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+        })
+    }
+
+    /// Takes either a url or a path to a local manifest file
+    pub fn from_url(value: &str) -> anyhow::Result<Self> {
+        if value.contains("://") {
+            return Ok(Self::Url {
+                url: value.to_string(),
+            });
+        }
+
+        let path = std::fs::canonicalize(value)
+            .with_context(|| format!("locating manifest file {value}"))?;
 
-    let release = define_release(&repo, explicit_tag).await?;
+        let url = reqwest::Url::from_file_path(&path)
+            .map_err(|_| anyhow::anyhow!("invalid manifest path {}", path.display()))?;
+
+        Ok(Self::Url {
+            url: url.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for ManifestSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Repo { owner, repo } => write!(f, "{owner}/{repo} releases"),
+            Self::Url { url } => write!(f, "{url}"),
+        }
+    }
+}
+
+/// What `manifest.source.json` keeps about the last manifest of a channel
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedSource {
+    #[serde(flatten)]
+    pub source: ManifestSource,
+
+    /// Whether the manifest was signed, in which case the next ones must be
+    #[serde(default)]
+    pub signed: bool,
+}
+
+fn recorded_manifest_source(config: &Config) -> anyhow::Result<Option<RecordedSource>> {
+    let source_file = config.manifest_source_file();
+
+    if !source_file.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(source_file).context("reading manifest source file")?;
+
+    let recorded = serde_json::from_str(&content).context("parsing manifest source file")?;
+
+    Ok(Some(recorded))
+}
+
+/// Whether the manifest last stored for the channel was signed, so that a
+/// removed signature can't pass for a manifest that never had one
+pub fn previously_signed(config: &Config) -> anyhow::Result<bool> {
+    Ok(recorded_manifest_source(config)?.is_some_and(|recorded| recorded.signed))
+}
+
+/// The manifest source of the channel: the one given explicitly, else the
+/// one recorded by the last download, else the official toolchain repo.
+pub async fn manifest_source(config: &Config) -> anyhow::Result<ManifestSource> {
+    if let Some(explicit) = config.manifest_source()? {
+        return Ok(explicit);
+    }
+
+    Ok(recorded_manifest_source(config)?
+        .map(|recorded| recorded.source)
+        .unwrap_or_default())
+}
+
+/// Fetches the manifest and its signature from a toolchain release, returning
+/// the tag of the release too
+async fn fetch_release_manifest(
+    owner: &str,
+    repo: &str,
+    explicit_tag: Option<&str>,
+    config: &Config,
+) -> anyhow::Result<(String, Option<String>, String)> {
+    let source = sources::open(&config.source_root().for_repo(owner, repo), config)?;
+
+    let release = define_release(source.as_ref(), explicit_tag).await?;
 
     let manifest_name = format!("manifest-{}.json", config.ensure_channel());
 
     let manifest_asset = release
-        .assets
-        .iter()
-        .find(|asset| asset.name == manifest_name)
+        .asset_by_name(&manifest_name)
         .ok_or_else(|| anyhow::anyhow!("No manifest asset found in latest release"))?;
 
-    let manifest_content = fetch_manifest_content(manifest_asset.browser_download_url.as_ref())
+    let signature_name = format!("{manifest_name}.minisig");
+
+    let manifest_content = fetch_manifest_content(&manifest_asset.url, config)
         .await
         .context("fetching manifest")?;
 
+    let signature_content = match release.asset_by_name(&signature_name) {
+        Some(asset) => Some(
+            fetch_manifest_content(&asset.url, config)
+                .await
+                .context("fetching manifest signature")?,
+        ),
+        None => None,
+    };
+
+    Ok((manifest_content, signature_content, release.tag))
+}
+
+pub async fn download_remote_manifest(
+    config: &Config,
+    explicit_tag: Option<&str>,
+) -> anyhow::Result<()> {
+    let source = manifest_source(config).await?;
+
+    let (manifest_content, signature_content, tag) = match &source {
+        ManifestSource::Repo { owner, repo } => {
+            let (manifest, signature, tag) =
+                fetch_release_manifest(owner, repo, explicit_tag, config).await?;
+
+            (manifest, signature, Some(tag))
+        }
+        ManifestSource::Url { url } => {
+            if let Some(explicit) = explicit_tag {
+                anyhow::bail!(
+                    "release {explicit} was requested but the manifest of this channel comes from {url}"
+                );
+            }
+
+            let manifest = fetch_manifest_content(url, config)
+                .await
+                .context("fetching manifest")?;
+
+            let signature = fetch_manifest_signature(&format!("{url}.minisig"), config).await?;
+
+            (manifest, signature, None)
+        }
+    };
+
+    signature::verify_manifest(
+        manifest_content.as_bytes(),
+        signature_content.as_deref(),
+        previously_signed(config)?,
+        config,
+    )
+    .context("verifying manifest signature")?;
+
     // ensure manifest is valid json and matches the format
     let _: Manifest = serde_json::from_str(&manifest_content).context("parsing manifest file")?;
 
@@ -142,6 +450,46 @@ pub async fn download_remote_manifest(
         .await
         .context("writing manifest file")?;
 
+    // a signature left from a previous download doesn't belong to this one
+    let signature_file = config.manifest_signature_file();
+
+    match &signature_content {
+        Some(signature) => fs::write(&signature_file, signature)
+            .await
+            .context("writing manifest signature file")?,
+        None if signature_file.exists() => fs::remove_file(&signature_file)
+            .await
+            .context("removing manifest signature file")?,
+        None => (),
+    }
+
+    // later calls on this channel keep using the same source
+    let recorded = RecordedSource {
+        source,
+        signed: signature_content.is_some(),
+    };
+
+    fs::write(
+        config.manifest_source_file(),
+        serde_json::to_string_pretty(&recorded)?,
+    )
+    .await
+    .context("writing manifest source file")?;
+
+    // keep track of tagged manifests so that switching back to the latest one
+    // (or to another tag) triggers a new download
+    let release_file = config.manifest_release_file();
+
+    if let Some(tag) = tag.filter(|_| explicit_tag.is_some()) {
+        fs::write(&release_file, tag)
+            .await
+            .context("writing manifest release file")?;
+    } else if release_file.exists() {
+        fs::remove_file(&release_file)
+            .await
+            .context("removing manifest release file")?;
+    }
+
     Ok(())
 }
 
@@ -156,9 +504,65 @@ pub async fn load_local_manifest(config: &Config) -> anyhow::Result<Option<Manif
         .await
         .context("reading manifest file")?;
 
-    let manifest: Manifest =
+    let signature_file = config.manifest_signature_file();
+
+    let signature_content = if signature_file.exists() {
+        Some(
+            fs::read_to_string(signature_file)
+                .await
+                .context("reading manifest signature file")?,
+        )
+    } else {
+        None
+    };
+
+    // the cached copy could have been tampered with after download
+    signature::verify_manifest(
+        manifest_content.as_bytes(),
+        signature_content.as_deref(),
+        previously_signed(config)?,
+        config,
+    )
+    .context("verifying cached manifest signature")?;
+
+    let mut manifest: Manifest =
         serde_json::from_str(&manifest_content).context("parsing manifest file")?;
 
+    let overrides = overrides::load_overrides(config).await?;
+
+    // a tool can be dropped from the manifest after it was overridden
+    let stale = manifest.apply_overrides(&overrides);
+
+    if !stale.is_empty() {
+        for name in stale.iter() {
+            eprintln!("warning: dropping the override of {name}, it's not in the manifest anymore");
+        }
+
+        overrides::reset_overrides(&stale, config).await?;
+    }
+
+    // the project asks for exactly its set, whatever was overridden for the
+    // channel from elsewhere
+    if let Some(project) = config.project() {
+        for (name, pinned) in project.pin.tools.iter() {
+            if let Some(overridden) = overrides.get(name)
+                && overridden != pinned
+            {
+                eprintln!(
+                    "warning: ignoring the override of {name} ({overridden}), {} pins it to {pinned}",
+                    project.path.display()
+                );
+            }
+        }
+
+        for name in manifest.apply_overrides(&project.pin.tools) {
+            eprintln!(
+                "warning: ignoring the pin of {name} in {}, it's not in the manifest anymore",
+                project.path.display()
+            );
+        }
+    }
+
     Ok(Some(manifest))
 }
 
@@ -186,14 +590,82 @@ fn manifest_is_stale(timestamp: Option<SystemTime>) -> bool {
     timestamp.is_none() || timestamp.unwrap() < SystemTime::now() - MANIFEST_STALE_THRESHOLD
 }
 
+/// Whether the cached manifest is due for a refresh
+pub async fn local_manifest_is_stale(config: &Config) -> anyhow::Result<bool> {
+    Ok(manifest_is_stale(check_manifest_timestamp(config).await?))
+}
+
+async fn cached_release(config: &Config) -> Option<String> {
+    fs::read_to_string(config.manifest_release_file())
+        .await
+        .ok()
+}
+
+async fn is_other_source(config: &Config) -> anyhow::Result<bool> {
+    match config.manifest_source()? {
+        Some(explicit) => {
+            let recorded = recorded_manifest_source(config)?.map(|recorded| recorded.source);
+            Ok(recorded != Some(explicit))
+        }
+        None => Ok(false),
+    }
+}
+
+/// Loads the cached manifest without going to the network, failing if it's
+/// missing or doesn't match what was asked for.
+async fn load_offline_manifest(config: &Config, tag: Option<&str>) -> anyhow::Result<Manifest> {
+    let channel = config.ensure_channel();
+    let manifest_file = config.manifest_file();
+
+    if !manifest_file.exists() {
+        anyhow::bail!(
+            "offline: no cached manifest for channel {channel} at {}",
+            manifest_file.display()
+        );
+    }
+
+    let cached = cached_release(config).await;
+
+    if tag != cached.as_deref() {
+        anyhow::bail!(
+            "offline: the cached manifest of channel {channel} comes from release {}, not {}",
+            cached.as_deref().unwrap_or("latest"),
+            tag.unwrap_or("latest")
+        );
+    }
+
+    if is_other_source(config).await? {
+        anyhow::bail!(
+            "offline: the cached manifest of channel {channel} comes from {}",
+            manifest_source(config).await?
+        );
+    }
+
+    let manifest = load_local_manifest(config)
+        .await?
+        .ok_or(anyhow::anyhow!("Manifest file should exist"))?;
+
+    Ok(manifest)
+}
+
 pub async fn load_latest_manifest(
     config: &Config,
     force_download: bool,
 ) -> anyhow::Result<Manifest> {
+    let pinned = config.pinned_release();
+
+    if config.offline() {
+        return load_offline_manifest(config, pinned).await;
+    }
+
     let timestamp = check_manifest_timestamp(config).await?;
 
-    if manifest_is_stale(timestamp) || force_download {
-        download_remote_manifest(config, None).await?;
+    let other_release = pinned != cached_release(config).await.as_deref();
+
+    let other_source = is_other_source(config).await?;
+
+    if manifest_is_stale(timestamp) || other_release || other_source || force_download {
+        download_remote_manifest(config, pinned).await?;
     }
 
     let manifest = load_local_manifest(config)
@@ -204,6 +676,10 @@ pub async fn load_latest_manifest(
 }
 
 pub async fn load_tagged_manifest(config: &Config, explicit_tag: &str) -> anyhow::Result<Manifest> {
+    if config.offline() {
+        return load_offline_manifest(config, Some(explicit_tag)).await;
+    }
+
     download_remote_manifest(config, Some(explicit_tag)).await?;
 
     let manifest = load_local_manifest(config)
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use anyhow::Context as _;
+use tokio::fs;
+
+use crate::Config;
+
+/// Loads the version requirements the user asked for explicitly, keyed by
+/// tool name. These take precedence over the manifest, but a project pin
+/// takes precedence over them.
+pub async fn load_overrides(config: &Config) -> anyhow::Result<HashMap<String, String>> {
+    let overrides_file = config.overrides_file();
+
+    if !overrides_file.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let content = fs::read_to_string(overrides_file)
+        .await
+        .context("reading overrides file")?;
+
+    serde_json::from_str(&content).context("parsing overrides file")
+}
+
+async fn write_overrides(
+    overrides: &HashMap<String, String>,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let overrides_file = config.overrides_file();
+
+    if overrides.is_empty() {
+        if overrides_file.exists() {
+            fs::remove_file(overrides_file)
+                .await
+                .context("removing overrides file")?;
+        }
+
+        return Ok(());
+    }
+
+    fs::create_dir_all(config.channel_dir())
+        .await
+        .context("creating channel dir")?;
+
+    fs::write(overrides_file, serde_json::to_string_pretty(overrides)?)
+        .await
+        .context("writing overrides file")?;
+
+    Ok(())
+}
+
+pub async fn save_overrides(
+    requested: &HashMap<String, String>,
+    config: &Config,
+) -> anyhow::Result<()> {
+    if requested.is_empty() {
+        return Ok(());
+    }
+
+    let mut overrides = load_overrides(config).await?;
+    overrides.extend(requested.clone());
+
+    write_overrides(&overrides, config).await
+}
+
+/// Drops the overrides of the given tools, or every override if no tool is
+/// named.
+pub async fn reset_overrides(names: &[String], config: &Config) -> anyhow::Result<()> {
+    let mut overrides = load_overrides(config).await?;
+
+    if names.is_empty() {
+        overrides.clear();
+    } else {
+        overrides.retain(|name, _| !names.contains(name));
+    }
+
+    write_overrides(&overrides, config).await
+}
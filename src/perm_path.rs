@@ -1,7 +1,7 @@
 use anyhow::Context;
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::Config;
 
@@ -39,7 +39,7 @@ fn source_cmd(root_dir: &Path) -> String {
     format!(
         r#"
 export TX3_ROOT="{}"
-export PATH="$TX3_ROOT/default/bin:$PATH"
+export PATH="$TX3_ROOT/bin:$PATH"
 "#,
         root_dir.to_str().unwrap()
     )
@@ -50,7 +50,7 @@ fn source_cmd_for_shell(shell: &KnownShell, root_dir: &Path) -> String {
         KnownShell::Fish => format!(
             r#"
 set -gx TX3_ROOT "{}"
-set -gx PATH "$TX3_ROOT/default/bin" $PATH
+set -gx PATH "$TX3_ROOT/bin" $PATH
 "#,
             root_dir.to_str().unwrap()
         ),
@@ -58,6 +58,27 @@ set -gx PATH "$TX3_ROOT/default/bin" $PATH
     }
 }
 
+/// What older versions appended, before the shims made the default channel
+/// bin dir unnecessary on PATH
+fn legacy_source_cmd_for_shell(shell: &KnownShell, root_dir: &Path) -> String {
+    match shell {
+        KnownShell::Fish => format!(
+            r#"
+set -gx TX3_ROOT "{}"
+set -gx PATH "$TX3_ROOT/default/bin" $PATH
+"#,
+            root_dir.to_str().unwrap()
+        ),
+        _ => format!(
+            r#"
+export TX3_ROOT="{}"
+export PATH="$TX3_ROOT/default/bin:$PATH"
+"#,
+            root_dir.to_str().unwrap()
+        ),
+    }
+}
+
 fn file_contains(profile_path: &Path, source_cmd: &str) -> bool {
     let contents = std::fs::read_to_string(profile_path).unwrap();
     contents.contains(source_cmd)
@@ -108,6 +129,89 @@ fn update_all_profiles(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn strip_file(profile_path: &Path, source_cmd: &str) -> anyhow::Result<()> {
+    println!(
+        "Removing from profile file: {}",
+        profile_path.to_str().unwrap()
+    );
+
+    let contents = std::fs::read_to_string(profile_path)?;
+    std::fs::write(profile_path, contents.replace(source_cmd, ""))?;
+
+    Ok(())
+}
+
+/// Removes the lines appended by `update_all_profiles` from every known shell
+/// profile file.
+pub fn remove_from_all_profiles(config: &Config, dry_run: bool) -> anyhow::Result<()> {
+    for sh in known_shells() {
+        let source_cmds = [
+            source_cmd_for_shell(&sh, &config.root_dir()),
+            legacy_source_cmd_for_shell(&sh, &config.root_dir()),
+        ];
+
+        for rc in sh.rc_files() {
+            let profile_path = dirs::home_dir()
+                .context("can't find user's home dir")?
+                .join(rc);
+
+            if !profile_path.exists() {
+                continue;
+            }
+
+            for source_cmd in source_cmds.iter() {
+                if !file_contains(&profile_path, source_cmd) {
+                    continue;
+                }
+
+                if dry_run {
+                    println!(
+                        "would remove the source command from: {}",
+                        profile_path.to_str().unwrap()
+                    );
+                    continue;
+                }
+
+                strip_file(&profile_path, source_cmd)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Shell profile files putting the root bin dir on PATH, including the ones
+/// wired by older versions
+pub fn wired_profiles(config: &Config) -> anyhow::Result<Vec<PathBuf>> {
+    let mut wired = vec![];
+
+    for sh in known_shells() {
+        let source_cmds = [
+            source_cmd_for_shell(&sh, &config.root_dir()),
+            legacy_source_cmd_for_shell(&sh, &config.root_dir()),
+        ];
+
+        for rc in sh.rc_files() {
+            let profile_path = dirs::home_dir()
+                .context("can't find user's home dir")?
+                .join(rc);
+
+            if !profile_path.exists() || wired.contains(&profile_path) {
+                continue;
+            }
+
+            if source_cmds
+                .iter()
+                .any(|source_cmd| file_contains(&profile_path, source_cmd))
+            {
+                wired.push(profile_path);
+            }
+        }
+    }
+
+    Ok(wired)
+}
+
 pub fn check_or_update(config: &Config) -> anyhow::Result<()> {
     update_all_profiles(config)?;
 
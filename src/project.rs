@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// Dedicated file to pin the toolchain of a project
+pub const TOOLCHAIN_FILE: &str = "tx3-toolchain.toml";
+
+/// Trix project file, which may carry a `[toolchain]` section too
+pub const TRIX_FILE: &str = "trix.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToolchainPin {
+    pub channel: Option<String>,
+
+    /// Tag of the `tx3-lang/toolchain` release to take the manifest from
+    pub release: Option<String>,
+
+    /// Version requirement overrides, keyed by tool name
+    #[serde(default)]
+    pub tools: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PinFile {
+    toolchain: Option<ToolchainPin>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectToolchain {
+    /// File the pin was read from
+    pub path: PathBuf,
+    pub pin: ToolchainPin,
+}
+
+fn read_pin(path: &Path) -> anyhow::Result<Option<ToolchainPin>> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+
+    let file: PinFile =
+        toml::from_str(&content).with_context(|| format!("parsing {}", path.display()))?;
+
+    Ok(file.toolchain)
+}
+
+/// Walks up from `start` looking for the closest file that pins the
+/// toolchain. A `tx3-toolchain.toml` wins over a `trix.toml` in the same
+/// directory.
+pub fn discover(start: &Path) -> anyhow::Result<Option<ProjectToolchain>> {
+    for dir in start.ancestors() {
+        for name in [TOOLCHAIN_FILE, TRIX_FILE] {
+            let path = dir.join(name);
+
+            if !path.is_file() {
+                continue;
+            }
+
+            if let Some(pin) = read_pin(&path)? {
+                return Ok(Some(ProjectToolchain { path, pin }));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn channel_at(start: &Path) -> (PathBuf, Option<String>) {
+        let found = discover(start).unwrap().unwrap();
+        (found.path, found.pin.channel)
+    }
+
+    #[test]
+    fn the_closest_pin_wins() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+
+        write(
+            &root.join(TOOLCHAIN_FILE),
+            "[toolchain]\nchannel = \"beta\"\n",
+        );
+        write(
+            &root.join("app").join(TRIX_FILE),
+            "[toolchain]\nchannel = \"stable\"\n\n[toolchain.tools]\ntrix = \"=0.11.2\"\n",
+        );
+
+        // walks up from nested dirs
+        let nested = root.join("app").join("src").join("lib");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let found = discover(&nested).unwrap().unwrap();
+        assert_eq!(found.path, root.join("app").join(TRIX_FILE));
+        assert_eq!(found.pin.channel.as_deref(), Some("stable"));
+        assert_eq!(found.pin.tools["trix"], "=0.11.2");
+
+        // a sibling dir only sees the outer one
+        let sibling = root.join("docs");
+        std::fs::create_dir_all(&sibling).unwrap();
+
+        assert_eq!(
+            channel_at(&sibling),
+            (root.join(TOOLCHAIN_FILE), Some("beta".to_string()))
+        );
+    }
+
+    #[test]
+    fn toolchain_file_wins_over_trix_file_in_the_same_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("app");
+
+        write(&dir.join(TRIX_FILE), "[toolchain]\nchannel = \"stable\"\n");
+        write(
+            &dir.join(TOOLCHAIN_FILE),
+            "[toolchain]\nchannel = \"nightly\"\n",
+        );
+
+        assert_eq!(
+            channel_at(&dir),
+            (dir.join(TOOLCHAIN_FILE), Some("nightly".to_string()))
+        );
+    }
+
+    #[test]
+    fn trix_files_without_a_toolchain_section_are_skipped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+
+        write(
+            &root.join(TOOLCHAIN_FILE),
+            "[toolchain]\nrelease = \"v0.8.0\"\n",
+        );
+        write(
+            &root.join("app").join(TRIX_FILE),
+            "[protocol]\nname = \"app\"\n",
+        );
+
+        let found = discover(&root.join("app")).unwrap().unwrap();
+        assert_eq!(found.path, root.join(TOOLCHAIN_FILE));
+        assert_eq!(found.pin.release.as_deref(), Some("v0.8.0"));
+    }
+
+    #[test]
+    fn broken_pin_files_fail() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("app");
+
+        write(&dir.join(TOOLCHAIN_FILE), "[toolchain\nchannel = ");
+
+        let err = discover(&dir).unwrap_err();
+        assert!(format!("{err:#}").contains(TOOLCHAIN_FILE), "{err:#}");
+    }
+}
@@ -0,0 +1,115 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Context as _;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::{Config, cache};
+
+/// What an install left in the channel for a tool and where it came from.
+/// Receipts written by older versions only list the extras.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Receipt {
+    /// Version the binary reported when it was installed
+    #[serde(default)]
+    pub version: Option<String>,
+
+    /// Tag of the tool release, unknown for installs from the download cache
+    #[serde(default)]
+    pub release: Option<String>,
+
+    /// Name of the release asset
+    #[serde(default)]
+    pub asset: Option<String>,
+
+    /// Url the asset was downloaded from
+    #[serde(default)]
+    pub source: Option<String>,
+
+    /// SHA-256 digest of the release asset
+    #[serde(default)]
+    pub sha256: Option<String>,
+
+    /// SHA-256 digest of the installed binary
+    #[serde(default)]
+    pub binary_sha256: Option<String>,
+
+    /// Unix timestamp of the install
+    #[serde(default)]
+    pub installed_at: u64,
+
+    /// Extra files, relative to the channel dir
+    #[serde(default)]
+    pub extras: Vec<PathBuf>,
+}
+
+impl Receipt {
+    pub fn version(&self) -> Option<Version> {
+        Version::parse(self.version.as_deref()?).ok()
+    }
+
+    /// How long ago the tool was installed, e.g. `3 days ago`
+    pub fn installed_ago(&self) -> String {
+        let elapsed = cache::now().saturating_sub(self.installed_at);
+
+        let (count, unit) = match elapsed {
+            0..60 => return "just now".to_string(),
+            60..3600 => (elapsed / 60, "minute"),
+            3600..86400 => (elapsed / 3600, "hour"),
+            _ => (elapsed / 86400, "day"),
+        };
+
+        let plural = if count == 1 { "" } else { "s" };
+
+        format!("{count} {unit}{plural} ago")
+    }
+}
+
+/// Receipts of the tools installed in the channel, keyed by tool name
+pub fn load_receipts(config: &Config) -> anyhow::Result<HashMap<String, Receipt>> {
+    let receipts_file = config.receipts_file();
+
+    if !receipts_file.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let content = std::fs::read_to_string(receipts_file).context("reading receipts file")?;
+
+    serde_json::from_str(&content).context("parsing receipts file")
+}
+
+pub fn save_receipts(receipts: &HashMap<String, Receipt>, config: &Config) -> anyhow::Result<()> {
+    std::fs::create_dir_all(config.channel_dir()).context("creating channel dir")?;
+
+    std::fs::write(
+        config.receipts_file(),
+        serde_json::to_string_pretty(receipts)?,
+    )
+    .context("writing receipts file")?;
+
+    Ok(())
+}
+
+/// Records the install of a tool, deleting the extras of the previous install
+/// that are not part of it anymore
+pub fn replace_receipt(name: &str, receipt: Receipt, config: &Config) -> anyhow::Result<()> {
+    let mut receipts = load_receipts(config)?;
+
+    if let Some(previous) = receipts.get(name) {
+        for stale in previous
+            .extras
+            .iter()
+            .filter(|e| !receipt.extras.contains(e))
+        {
+            let path = config.channel_dir().join(stale);
+
+            if path.exists() {
+                std::fs::remove_file(&path).context("removing stale file")?;
+            }
+        }
+    }
+
+    receipts.insert(name.to_string(), receipt);
+
+    save_receipts(&receipts, config)
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    Config, bin,
+    manifest::{Manifest, Tool},
+    receipts,
+};
+
+/// Copy of the `tx3up` executable in the shims dir, which every shim links
+/// to. Being a copy, it survives the original being upgraded or removed.
+fn proxy_path(config: &Config) -> PathBuf {
+    config.shims_dir().join(bin::file_name("tx3up"))
+}
+
+/// Names of the tools of the active channel, including the companion
+/// binaries they install, whose shims are never cleaned up. The manifest is
+/// only read for names here, it's verified when it's actually used.
+fn known_tools(config: &Config) -> HashSet<String> {
+    let mut known = HashSet::new();
+
+    if let Ok(content) = std::fs::read_to_string(config.manifest_file())
+        && let Ok(manifest) = serde_json::from_str::<Manifest>(&content)
+    {
+        let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+
+        known.extend(
+            manifest
+                .tools()
+                .filter(|tool| !tool.is_global && Some(tool.name.as_str()) != self_name)
+                .map(|tool| tool.name.clone()),
+        );
+    }
+
+    for (name, receipt) in receipts::load_receipts(config).unwrap_or_default() {
+        known.insert(name);
+
+        known.extend(
+            receipt
+                .extras
+                .iter()
+                .filter(|extra| extra.parent() == Some(Path::new("bin")))
+                .filter_map(|extra| Some(extra.file_stem()?.to_str()?.to_string())),
+        );
+    }
+
+    known
+}
+
+/// Returns the tool name when the current process was invoked through a
+/// shim rather than as `tx3up` itself. Whatever the shim is named, it never
+/// falls back to running `tx3up`, so a tool missing from the active channel
+/// fails in `run_proxy` instead.
+pub fn proxied_tool() -> Option<String> {
+    let arg0 = std::env::args_os().next()?;
+
+    let name = Path::new(&arg0).file_stem()?.to_string_lossy().into_owned();
+
+    (!name.starts_with("tx3up")).then_some(name)
+}
+
+/// Executes the real binary of `tool` from the channel that is active for
+/// this invocation, forwarding args, stdio and exit code.
+pub fn run_proxy(tool: &str, config: &Config) -> anyhow::Result<()> {
+    let channel = config
+        .channel()
+        .with_context(|| format!("can't run {tool}, pick a channel with `tx3up use <channel>`"))?;
+    let bin_path = config.bin_dir().join(bin::file_name(tool));
+
+    if !bin_path.exists() {
+        anyhow::bail!(
+            "{tool} is not installed in channel {channel}, run `tx3up install` to install it"
+        );
+    }
+
+    let mut cmd = std::process::Command::new(&bin_path);
+
+    // nested invocations of other tools should stick to the same channel
+    cmd.args(std::env::args_os().skip(1))
+        .env("TX3_CHANNEL", &channel);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = cmd.exec();
+        Err(err).with_context(|| format!("executing {}", bin_path.display()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = cmd
+            .status()
+            .with_context(|| format!("executing {}", bin_path.display()))?;
+
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+fn link_shim(proxy: &Path, shim: &Path) -> anyhow::Result<()> {
+    if shim.is_symlink() || shim.exists() {
+        std::fs::remove_file(shim).context("removing previous shim")?;
+    }
+
+    // hard links are cheap, but they can't cross filesystems
+    if std::fs::hard_link(proxy, shim).is_err() {
+        std::fs::copy(proxy, shim).context("copying shim")?;
+    }
+
+    Ok(())
+}
+
+/// Puts a copy of the running `tx3up` executable in the shims dir, unless
+/// it's the one running.
+fn install_proxy(config: &Config) -> anyhow::Result<PathBuf> {
+    let exe = bin::self_exe()?;
+    let proxy = proxy_path(config);
+
+    if proxy.canonicalize().ok().as_deref() == Some(exe) {
+        return Ok(proxy);
+    }
+
+    // a shim may be running the previous copy, so swap it in one go
+    let new = proxy.with_extension("new");
+    std::fs::copy(exe, &new).context("copying tx3up into the shims dir")?;
+    std::fs::rename(&new, &proxy).context("moving tx3up into the shims dir")?;
+
+    Ok(proxy)
+}
+
+async fn describe_binary(tool: &Tool, path: &Path) -> String {
+    let version = match bin::check_version(tool, path).await {
+        Ok(version) => format!("version {version}"),
+        Err(_) => "version not reported".to_string(),
+    };
+
+    format!("{} ({version})", path.display())
+}
+
+/// Warns when running a tool by name wouldn't reach the copy managed by
+/// tx3up, e.g. because a stale `cargo install` comes first on `PATH`
+pub async fn warn_if_shadowed(tool: &Tool, config: &Config) {
+    // global tools live wherever PATH finds them
+    if tool.is_global {
+        return;
+    }
+
+    let Some(shadowing) = bin::find_shadowing(&tool.name, config) else {
+        return;
+    };
+
+    println!(
+        "⚠️  `{}` on your PATH runs {}",
+        tool.name,
+        describe_binary(tool, &shadowing).await
+    );
+    println!(
+        "   instead of {}",
+        describe_binary(tool, &tool.bin_path(config)).await
+    );
+    println!(
+        "   remove it or put {} first on your PATH",
+        config.shims_dir().display()
+    );
+}
+
+/// Creates a shim in the root's shared bin dir for every tool managed by
+/// tx3up, linked to a copy of the running `tx3up` executable that sits next
+/// to them.
+pub fn install_shims(manifest: &Manifest, config: &Config) -> anyhow::Result<()> {
+    let shims_dir = config.shims_dir();
+    std::fs::create_dir_all(&shims_dir).context("creating shims dir")?;
+
+    let proxy = install_proxy(config)?;
+
+    let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+    let mut linked = HashSet::new();
+
+    for tool in manifest.tools() {
+        if tool.is_global || Some(tool.name.as_str()) == self_name {
+            continue;
+        }
+
+        let shim = shims_dir.join(tool.bin_file_name());
+        link_shim(&proxy, &shim)?;
+        linked.insert(shim);
+    }
+
+    // companion binaries installed along with a tool need a shim too
+    for receipt in receipts::load_receipts(config)?.values() {
+        for extra in receipt.extras.iter() {
+            if extra.parent() == Some(Path::new("bin"))
+                && let Some(name) = extra.file_name()
+            {
+                let shim = shims_dir.join(name);
+                link_shim(&proxy, &shim)?;
+                linked.insert(shim);
+            }
+        }
+    }
+
+    // drop shims of extras a new version no longer ships, unless another
+    // channel still provides them
+    let known = known_tools(config);
+
+    for shim in unused_shims(config, |_| false)? {
+        let is_known = shim
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| known.contains(stem));
+
+        if !linked.contains(&shim) && !is_known {
+            std::fs::remove_file(&shim)
+                .with_context(|| format!("removing unused shim {}", shim.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shims that no installed channel has a binary for, once the binaries for
+/// which `gone` returns true are removed.
+pub fn unused_shims(config: &Config, gone: impl Fn(&Path) -> bool) -> anyhow::Result<Vec<PathBuf>> {
+    let shims_dir = config.shims_dir();
+
+    if !shims_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut bin_dirs = vec![];
+
+    for entry in std::fs::read_dir(config.root_dir()).context("reading root dir")? {
+        let path = entry?.path();
+
+        // the default symlink points at a channel that's listed anyway
+        if path.is_symlink() || path == shims_dir {
+            continue;
+        }
+
+        if path.join("bin").is_dir() {
+            bin_dirs.push(path.join("bin"));
+        }
+    }
+
+    let proxy = proxy_path(config);
+    let mut unused = vec![];
+
+    for entry in std::fs::read_dir(&shims_dir).context("reading shims dir")? {
+        let shim = entry?.path();
+
+        if shim == proxy {
+            continue;
+        }
+
+        let Some(name) = shim.file_name() else {
+            continue;
+        };
+
+        let provided = bin_dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .any(|bin| bin.exists() && !gone(&bin));
+
+        if !provided {
+            unused.push(shim);
+        }
+    }
+
+    Ok(unused)
+}
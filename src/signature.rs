@@ -0,0 +1,78 @@
+use std::sync::Once;
+
+use anyhow::Context;
+use minisign_verify::{PublicKey, Signature};
+
+use crate::Config;
+
+/// Minisign public keys of the channel manifests published on
+/// `tx3-lang/toolchain` releases. None yet: the release pipeline doesn't sign
+/// manifests, so until it does only keys passed with `--trust-key` verify.
+const TOOLCHAIN_PUBLIC_KEYS: &[&str] = &[];
+
+fn trusted_keys(config: &Config) -> anyhow::Result<Vec<PublicKey>> {
+    let mut keys = vec![];
+
+    for embedded in TOOLCHAIN_PUBLIC_KEYS {
+        keys.push(PublicKey::from_base64(embedded).context("decoding embedded key")?);
+    }
+
+    for extra in config.extra_trusted_keys() {
+        let key = PublicKey::from_base64(extra)
+            .with_context(|| format!("decoding trusted key {extra}"))?;
+
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+/// Verifies a detached minisign signature of `content` against the embedded
+/// keys and any extra key trusted through the config.
+pub fn verify(content: &[u8], signature: &str, config: &Config) -> anyhow::Result<()> {
+    let signature = Signature::decode(signature).context("decoding signature")?;
+
+    for key in trusted_keys(config)? {
+        if key.verify(content, &signature, false).is_ok() {
+            return Ok(());
+        }
+    }
+
+    anyhow::bail!("signature doesn't match any trusted key")
+}
+
+static UNSIGNED_WARNING: Once = Once::new();
+
+/// Verifies the signature of a manifest. An unsigned one is only accepted
+/// with `--allow-unsigned`, and never when extra keys are trusted or when
+/// `signed_before` says the previous manifest of the channel was signed.
+pub fn verify_manifest(
+    content: &[u8],
+    signature: Option<&str>,
+    signed_before: bool,
+    config: &Config,
+) -> anyhow::Result<()> {
+    if let Some(signature) = signature {
+        return verify(content, signature, config);
+    }
+
+    if !config.extra_trusted_keys().is_empty() {
+        anyhow::bail!("the manifest is not signed, but trusted keys are set to check it");
+    }
+
+    if signed_before {
+        anyhow::bail!("the manifest is not signed, but the previous one of this channel was");
+    }
+
+    if !config.allow_unsigned() {
+        anyhow::bail!(
+            "the manifest is not signed, pass --allow-unsigned (TX3_ALLOW_UNSIGNED=1) to accept it"
+        );
+    }
+
+    UNSIGNED_WARNING.call_once(|| {
+        eprintln!("warning: the manifest is not signed, skipping signature verification");
+    });
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use octocrab::Octocrab;
+
+use super::{Asset, Release, ReleaseSource};
+use crate::Config;
+
+pub fn build_octocrab(config: &Config) -> anyhow::Result<Octocrab> {
+    let mut builder = Octocrab::builder();
+
+    if let Some(token) = config.github_token() {
+        builder = builder.personal_token(token.to_string());
+    }
+
+    builder.build().context("building octocrab client")
+}
+
+fn is_not_found(err: &octocrab::Error) -> bool {
+    matches!(err, octocrab::Error::GitHub { source, .. } if source.status_code == 404)
+}
+
+fn is_unauthorized(err: &octocrab::Error) -> bool {
+    matches!(err, octocrab::Error::GitHub { source, .. } if source.status_code == 401)
+}
+
+/// Core API quota left for the configured token, or for anonymous requests
+/// without one. Returns `None` when GitHub rejects the token.
+pub async fn core_rate_limit(config: &Config) -> anyhow::Result<Option<octocrab::models::Rate>> {
+    match build_octocrab(config)?.ratelimit().get().await {
+        Ok(limit) => Ok(Some(limit.resources.core)),
+        Err(err) if is_unauthorized(&err) => Ok(None),
+        Err(err) => Err(err).context("querying GitHub rate limit"),
+    }
+}
+
+impl From<octocrab::models::repos::Release> for Release {
+    fn from(release: octocrab::models::repos::Release) -> Self {
+        Self {
+            tag: release.tag_name,
+            draft: release.draft,
+            prerelease: release.prerelease,
+            assets: release
+                .assets
+                .into_iter()
+                .map(|asset| Asset {
+                    name: asset.name,
+                    url: asset.browser_download_url.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+pub struct GithubSource {
+    octocrab: Octocrab,
+    owner: String,
+    repo: String,
+}
+
+impl GithubSource {
+    pub fn new(owner: &str, repo: &str, config: &Config) -> anyhow::Result<Self> {
+        Ok(Self {
+            octocrab: build_octocrab(config)?,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl ReleaseSource for GithubSource {
+    async fn list_releases(&self) -> anyhow::Result<Vec<Release>> {
+        let first_page = self
+            .octocrab
+            .repos(&self.owner, &self.repo)
+            .releases()
+            .list()
+            .per_page(100)
+            .send()
+            .await
+            .context("Failed to list releases")?;
+
+        let releases = self
+            .octocrab
+            .all_pages(first_page)
+            .await
+            .context("Failed to list releases")?;
+
+        Ok(releases.into_iter().map(Release::from).collect())
+    }
+
+    async fn release_by_tag(&self, tag: &str) -> anyhow::Result<Option<Release>> {
+        let release = self
+            .octocrab
+            .repos(&self.owner, &self.repo)
+            .releases()
+            .get_by_tag(tag)
+            .await;
+
+        match release {
+            Ok(release) => Ok(Some(release.into())),
+            Err(err) if is_not_found(&err) => Ok(None),
+            Err(err) => Err(err).context("fetching release"),
+        }
+    }
+
+    async fn latest_release(&self) -> anyhow::Result<Option<Release>> {
+        let release = self
+            .octocrab
+            .repos(&self.owner, &self.repo)
+            .releases()
+            .get_latest()
+            .await;
+
+        match release {
+            Ok(release) => Ok(Some(release.into())),
+            Err(err) if is_not_found(&err) => Ok(None),
+            Err(err) => Err(err).context("fetching latest release"),
+        }
+    }
+}
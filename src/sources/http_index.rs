@@ -0,0 +1,163 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::{Release, ReleaseSource};
+use crate::{
+    Config,
+    download::{self, DownloadOptions},
+};
+
+/// Static JSON listing of the releases of a repo, asset urls may be relative
+/// to the index itself
+#[derive(Debug, Deserialize)]
+struct Index {
+    releases: Vec<Release>,
+}
+
+pub struct HttpIndexSource {
+    url: String,
+    options: DownloadOptions,
+}
+
+impl HttpIndexSource {
+    pub fn new(url: &str, config: &Config) -> Self {
+        Self {
+            url: url.to_string(),
+            options: DownloadOptions::from_config(config),
+        }
+    }
+}
+
+#[async_trait]
+impl ReleaseSource for HttpIndexSource {
+    async fn list_releases(&self) -> anyhow::Result<Vec<Release>> {
+        let content = download::fetch_text(&self.url, &self.options)
+            .await
+            .context("fetching release index")?;
+
+        let index: Index = serde_json::from_str(&content).context("parsing release index")?;
+
+        let base = Url::parse(&self.url).context("parsing release index url")?;
+
+        let mut releases = index.releases;
+
+        for asset in releases.iter_mut().flat_map(|r| r.assets.iter_mut()) {
+            asset.url = base
+                .join(&asset.url)
+                .context("resolving asset url")?
+                .to_string();
+        }
+
+        Ok(releases)
+    }
+
+    async fn release_by_tag(&self, tag: &str) -> anyhow::Result<Option<Release>> {
+        let releases = self.list_releases().await?;
+
+        Ok(releases.into_iter().find(|release| release.tag == tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    const INDEX: &str = r#"{
+        "releases": [
+            {
+                "tag": "v0.1.0",
+                "assets": [
+                    {"name": "trix-x86_64-unknown-linux-gnu.tar.gz", "url": "v0.1.0/trix-x86_64-unknown-linux-gnu.tar.gz"},
+                    {"name": "trix-aarch64-apple-darwin.tar.gz", "url": "https://mirror.example.com/trix-aarch64-apple-darwin.tar.gz"}
+                ]
+            },
+            {
+                "tag": "v0.2.0-rc.1",
+                "prerelease": true,
+                "assets": []
+            }
+        ]
+    }"#;
+
+    /// Serves `body` to every request, returning the url of the index
+    async fn serve(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 4096];
+                let _ = socket.read(&mut request).await;
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        format!("http://{addr}/o/trix/index.json")
+    }
+
+    fn source(url: &str) -> HttpIndexSource {
+        HttpIndexSource {
+            url: url.to_string(),
+            options: DownloadOptions {
+                retries: 0,
+                timeout: Duration::from_secs(5),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_asset_urls_against_the_index() {
+        let url = serve(INDEX).await;
+        let base = url.trim_end_matches("index.json");
+
+        let releases = source(&url).list_releases().await.unwrap();
+        assert_eq!(releases.len(), 2);
+
+        let assets = &releases[0].assets;
+        assert_eq!(
+            assets[0].url,
+            format!("{base}v0.1.0/trix-x86_64-unknown-linux-gnu.tar.gz")
+        );
+        assert_eq!(
+            assets[1].url,
+            "https://mirror.example.com/trix-aarch64-apple-darwin.tar.gz"
+        );
+    }
+
+    #[tokio::test]
+    async fn finds_releases_by_tag_and_skips_prereleases_for_latest() {
+        let url = serve(INDEX).await;
+        let source = source(&url);
+
+        let tagged = source.release_by_tag("v0.2.0-rc.1").await.unwrap().unwrap();
+        assert!(tagged.prerelease);
+
+        assert!(source.release_by_tag("v9.9.9").await.unwrap().is_none());
+
+        let latest = source.latest_release().await.unwrap().unwrap();
+        assert_eq!(latest.tag, "v0.1.0");
+    }
+
+    #[tokio::test]
+    async fn fails_on_a_malformed_index() {
+        let url = serve("<html>not found</html>").await;
+
+        let err = source(&url).list_releases().await.unwrap_err();
+        assert_eq!(err.to_string(), "parsing release index");
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Url;
+
+use super::{Asset, Release, ReleaseSource};
+
+/// Releases laid out on disk as `<dir>/<tag>/<asset>`
+pub struct LocalDirSource {
+    dir: PathBuf,
+}
+
+impl LocalDirSource {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn read_release(&self, tag: &str) -> anyhow::Result<Release> {
+        let release_dir = self.dir.join(tag);
+        let mut assets = vec![];
+
+        for entry in std::fs::read_dir(&release_dir).context("reading release dir")? {
+            let entry = entry?;
+
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+
+            let path = entry.path().canonicalize()?;
+
+            let url = Url::from_file_path(&path)
+                .map_err(|_| anyhow::anyhow!("invalid asset path {}", path.display()))?;
+
+            assets.push(Asset {
+                name: entry.file_name().to_string_lossy().to_string(),
+                url: url.to_string(),
+            });
+        }
+
+        let mut release = Release {
+            tag: tag.to_string(),
+            draft: false,
+            prerelease: false,
+            assets,
+        };
+
+        release.prerelease = release.version().is_some_and(|v| !v.pre.is_empty());
+
+        Ok(release)
+    }
+}
+
+#[async_trait]
+impl ReleaseSource for LocalDirSource {
+    async fn list_releases(&self) -> anyhow::Result<Vec<Release>> {
+        if !self.dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut releases = vec![];
+
+        for entry in std::fs::read_dir(&self.dir).context("reading releases dir")? {
+            let entry = entry?;
+
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            releases.push(self.read_release(&entry.file_name().to_string_lossy())?);
+        }
+
+        Ok(releases)
+    }
+
+    async fn release_by_tag(&self, tag: &str) -> anyhow::Result<Option<Release>> {
+        if !self.dir.join(tag).is_dir() {
+            return Ok(None);
+        }
+
+        Ok(Some(self.read_release(tag)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::{self, DownloadOptions};
+
+    fn write_asset(dir: &Path, tag: &str, name: &str) {
+        std::fs::create_dir_all(dir.join(tag)).unwrap();
+        std::fs::write(dir.join(tag).join(name), format!("{tag}/{name}")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn lists_one_release_per_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("o").join("trix");
+
+        write_asset(&dir, "v0.1.0", "trix-x86_64-unknown-linux-gnu.tar.gz");
+        write_asset(&dir, "v0.2.0", "trix-x86_64-unknown-linux-gnu.tar.gz");
+        write_asset(
+            &dir,
+            "v0.3.0-beta.1",
+            "trix-x86_64-unknown-linux-gnu.tar.gz",
+        );
+        std::fs::write(dir.join("README"), "not a release").unwrap();
+
+        let source = LocalDirSource::new(&dir);
+
+        let mut tags: Vec<_> = source
+            .list_releases()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|release| (release.tag, release.prerelease))
+            .collect();
+        tags.sort();
+
+        assert_eq!(
+            tags,
+            vec![
+                ("v0.1.0".to_string(), false),
+                ("v0.2.0".to_string(), false),
+                ("v0.3.0-beta.1".to_string(), true),
+            ]
+        );
+
+        let latest = source.latest_release().await.unwrap().unwrap();
+        assert_eq!(latest.tag, "v0.2.0");
+    }
+
+    #[tokio::test]
+    async fn asset_urls_survive_unusual_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("with space").join("100%");
+
+        write_asset(&dir, "v0.1.0", "trix-x86_64-unknown-linux-gnu.tar.gz");
+
+        let release = LocalDirSource::new(&dir)
+            .release_by_tag("v0.1.0")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let asset = release
+            .asset_by_name("trix-x86_64-unknown-linux-gnu.tar.gz")
+            .unwrap();
+
+        let options = DownloadOptions {
+            retries: 0,
+            timeout: std::time::Duration::from_secs(5),
+        };
+
+        let content = download::fetch_text(&asset.url, &options).await.unwrap();
+        assert_eq!(content, "v0.1.0/trix-x86_64-unknown-linux-gnu.tar.gz");
+    }
+
+    #[tokio::test]
+    async fn missing_dirs_have_no_releases() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = LocalDirSource::new(&tmp.path().join("nope"));
+
+        assert!(source.list_releases().await.unwrap().is_empty());
+        assert!(source.release_by_tag("v0.1.0").await.unwrap().is_none());
+    }
+}
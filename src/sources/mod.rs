@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::Config;
+
+mod github;
+mod http_index;
+mod local_dir;
+
+pub use github::core_rate_limit;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Asset {
+    pub name: String,
+
+    /// Where to download the asset from, either an http(s) or a file:// url
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    pub tag: String,
+
+    #[serde(default)]
+    pub draft: bool,
+
+    #[serde(default)]
+    pub prerelease: bool,
+
+    pub assets: Vec<Asset>,
+}
+
+impl Release {
+    pub fn version(&self) -> Option<Version> {
+        let sanitized = self.tag.strip_prefix('v').unwrap_or(&self.tag);
+
+        Version::parse(sanitized).ok()
+    }
+
+    pub fn asset_by_name(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// Picks the highest stable version, which is what "latest" means for
+/// sources that don't keep track of it themselves.
+fn latest_of(releases: Vec<Release>) -> Option<Release> {
+    releases
+        .into_iter()
+        .filter(|release| !release.draft && !release.prerelease)
+        .filter_map(|release| Some((release.version()?, release)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+/// Somewhere releases of a single repo can be looked up from
+#[async_trait]
+pub trait ReleaseSource: Send + Sync {
+    async fn list_releases(&self) -> anyhow::Result<Vec<Release>>;
+
+    async fn release_by_tag(&self, tag: &str) -> anyhow::Result<Option<Release>>;
+
+    async fn latest_release(&self) -> anyhow::Result<Option<Release>> {
+        Ok(latest_of(self.list_releases().await?))
+    }
+}
+
+/// Where the releases of a single repo live
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceConfig {
+    Github { owner: String, repo: String },
+    HttpIndex { url: String },
+    LocalDir { path: PathBuf },
+}
+
+/// Backend used for every repo that doesn't declare its own source. Index and
+/// dir roots hold one entry per repo at `<root>/<owner>/<repo>`.
+#[derive(Debug, Clone, Default)]
+pub enum SourceRoot {
+    #[default]
+    Github,
+    HttpIndex(String),
+    LocalDir(PathBuf),
+}
+
+impl std::str::FromStr for SourceRoot {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "github" {
+            return Ok(Self::Github);
+        }
+
+        if let Some(url) = s.strip_prefix("index:") {
+            return Ok(Self::HttpIndex(url.trim_end_matches('/').to_string()));
+        }
+
+        if let Some(path) = s.strip_prefix("dir:") {
+            return Ok(Self::LocalDir(PathBuf::from(path)));
+        }
+
+        anyhow::bail!("unknown release source {s}, expected github, index:<url> or dir:<path>")
+    }
+}
+
+impl SourceRoot {
+    pub fn for_repo(&self, owner: &str, repo: &str) -> SourceConfig {
+        match self {
+            SourceRoot::Github => SourceConfig::Github {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+            },
+            SourceRoot::HttpIndex(base) => SourceConfig::HttpIndex {
+                url: format!("{base}/{owner}/{repo}/index.json"),
+            },
+            SourceRoot::LocalDir(root) => SourceConfig::LocalDir {
+                path: root.join(owner).join(repo),
+            },
+        }
+    }
+}
+
+pub fn open(source: &SourceConfig, config: &Config) -> anyhow::Result<Box<dyn ReleaseSource>> {
+    let source: Box<dyn ReleaseSource> = match source {
+        SourceConfig::Github { owner, repo } => {
+            Box::new(github::GithubSource::new(owner, repo, config)?)
+        }
+        SourceConfig::HttpIndex { url } => Box::new(http_index::HttpIndexSource::new(url, config)),
+        SourceConfig::LocalDir { path } => Box::new(local_dir::LocalDirSource::new(path)),
+    };
+
+    Ok(source)
+}
@@ -0,0 +1,308 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::Config;
+
+/// Records every replacement before it happens, one JSON line each, so the
+/// backups of an interrupted run can be put back.
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+#[derive(Serialize, Deserialize)]
+struct Replaced {
+    target: PathBuf,
+    backup: Option<PathBuf>,
+}
+
+impl Replaced {
+    /// Puts the target back the way it was. Safe to call again if a previous
+    /// restore was interrupted.
+    fn restore(&self) -> anyhow::Result<()> {
+        match &self.backup {
+            Some(backup) if backup.exists() => {
+                std::fs::rename(backup, &self.target).context("restoring backup")?
+            }
+            // the backup was never taken or is already back in place
+            Some(_) => (),
+            None if self.target.exists() => {
+                std::fs::remove_file(&self.target).context("removing new binary")?
+            }
+            None => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Restores the files replaced by a run that didn't get to commit or roll
+/// back, e.g. because it was killed halfway through.
+fn recover(backup_dir: &Path) -> anyhow::Result<()> {
+    let journal = backup_dir.join(JOURNAL_FILE);
+
+    if !journal.exists() {
+        return Ok(());
+    }
+
+    let content = std::fs::read_to_string(&journal).context("reading install journal")?;
+
+    // a crash while appending leaves at most a truncated last line
+    let entries: Vec<Replaced> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    for replaced in entries.iter().rev() {
+        eprintln!(
+            "> Restoring {} from an interrupted install",
+            replaced.target.display()
+        );
+        replaced.restore()?;
+    }
+
+    std::fs::remove_file(journal).context("removing install journal")?;
+
+    Ok(())
+}
+
+/// Tracks the binaries swapped into the channel's bin dir during an install
+/// run, so they can be restored if a later tool fails.
+pub struct Transaction {
+    staging_dir: PathBuf,
+    backup_dir: PathBuf,
+    replaced: Vec<Replaced>,
+}
+
+impl Transaction {
+    pub fn begin(config: &Config) -> anyhow::Result<Self> {
+        let staging_dir = config.staging_dir();
+        let backup_dir = config.backup_dir();
+
+        recover(&backup_dir)?;
+
+        // once restored, leftovers from an interrupted run are of no use
+        for dir in [&staging_dir, &backup_dir] {
+            if dir.exists() {
+                std::fs::remove_dir_all(dir).context("cleaning up previous run")?;
+            }
+
+            std::fs::create_dir_all(dir).context("creating staging dir")?;
+        }
+
+        Ok(Self {
+            staging_dir,
+            backup_dir,
+            replaced: vec![],
+        })
+    }
+
+    /// Returns an empty directory under the channel where a tool can be
+    /// downloaded and extracted before being moved into place.
+    pub fn stage_dir(&self, tool_name: &str) -> anyhow::Result<PathBuf> {
+        let dir = self.staging_dir.join(tool_name);
+
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).context("cleaning up stage dir")?;
+        }
+
+        std::fs::create_dir_all(&dir).context("creating stage dir")?;
+
+        Ok(dir)
+    }
+
+    fn record(&self, replaced: &Replaced) -> anyhow::Result<()> {
+        let mut journal = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.backup_dir.join(JOURNAL_FILE))
+            .context("opening install journal")?;
+
+        writeln!(journal, "{}", serde_json::to_string(replaced)?)
+            .and_then(|_| journal.sync_all())
+            .context("writing install journal")?;
+
+        Ok(())
+    }
+
+    /// Atomically moves a staged file to `target`, keeping a backup of any
+    /// file it replaces. The target is never missing, even if the process
+    /// dies halfway through.
+    pub fn replace(&mut self, staged: &Path, target: &Path) -> anyhow::Result<()> {
+        let file_name = target.file_name().context("invalid target path")?;
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).context("creating target dir")?;
+        }
+
+        // several files of a tool may share a name (e.g. a binary and its
+        // completion script)
+        let backup = target.exists().then(|| {
+            self.backup_dir.join(format!(
+                "{}-{}",
+                self.replaced.len(),
+                file_name.to_string_lossy()
+            ))
+        });
+
+        let replaced = Replaced {
+            target: target.to_path_buf(),
+            backup,
+        };
+
+        self.record(&replaced)?;
+
+        // the previous file stays in place while backed up, so the rename
+        // below is the only step that changes the target
+        if let Some(backup) = &replaced.backup
+            && std::fs::hard_link(target, backup).is_err()
+        {
+            std::fs::copy(target, backup).context("backing up previous binary")?;
+        }
+
+        self.replaced.push(replaced);
+
+        std::fs::rename(staged, target).context("moving staged binary into place")?;
+
+        Ok(())
+    }
+
+    /// Restores every replaced file to its previous state.
+    pub fn rollback(mut self) -> anyhow::Result<()> {
+        while let Some(replaced) = self.replaced.pop() {
+            eprintln!("> Restoring {}", replaced.target.display());
+            replaced.restore()?;
+        }
+
+        self.cleanup()
+    }
+
+    /// Keeps the new files and drops the backups.
+    pub fn commit(self) -> anyhow::Result<()> {
+        self.cleanup()
+    }
+
+    fn cleanup(&self) -> anyhow::Result<()> {
+        // without the journal the backups are never restored, so it goes first
+        let journal = self.backup_dir.join(JOURNAL_FILE);
+
+        if journal.exists() {
+            std::fs::remove_file(journal).context("removing install journal")?;
+        }
+
+        for dir in [&self.staging_dir, &self.backup_dir] {
+            if dir.exists() {
+                std::fs::remove_dir_all(dir).context("removing staging dir")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(tx: &Transaction, name: &str, content: &str) -> PathBuf {
+        let staged = tx.stage_dir(name).unwrap().join(name);
+        std::fs::write(&staged, content).unwrap();
+        staged
+    }
+
+    #[test]
+    fn rollback_restores_the_previous_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(tmp.path());
+
+        let target = config.bin_dir().join("trix");
+        std::fs::create_dir_all(config.bin_dir()).unwrap();
+        std::fs::write(&target, "old").unwrap();
+
+        let mut tx = Transaction::begin(&config).unwrap();
+        let staged = stage(&tx, "trix", "new");
+        tx.replace(&staged, &target).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+
+        tx.rollback().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "old");
+        assert!(!config.backup_dir().exists());
+    }
+
+    #[test]
+    fn rollback_removes_files_that_were_new() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(tmp.path());
+
+        let target = config.bin_dir().join("trix");
+
+        let mut tx = Transaction::begin(&config).unwrap();
+        let staged = stage(&tx, "trix", "new");
+        tx.replace(&staged, &target).unwrap();
+
+        assert!(target.exists());
+
+        tx.rollback().unwrap();
+
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn commit_keeps_the_new_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(tmp.path());
+
+        let target = config.bin_dir().join("trix");
+        std::fs::create_dir_all(config.bin_dir()).unwrap();
+        std::fs::write(&target, "old").unwrap();
+
+        let mut tx = Transaction::begin(&config).unwrap();
+        let staged = stage(&tx, "trix", "new");
+        tx.replace(&staged, &target).unwrap();
+        tx.commit().unwrap();
+
+        // a later run has nothing to recover
+        Transaction::begin(&config).unwrap().commit().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+    }
+
+    #[test]
+    fn begin_recovers_an_interrupted_run() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(tmp.path());
+
+        let replaced = config.bin_dir().join("trix");
+        let added = config.bin_dir().join("tx3-lsp");
+        std::fs::create_dir_all(config.bin_dir()).unwrap();
+        std::fs::write(&replaced, "old").unwrap();
+
+        let mut tx = Transaction::begin(&config).unwrap();
+
+        let staged = stage(&tx, "trix", "new");
+        tx.replace(&staged, &replaced).unwrap();
+
+        let staged = stage(&tx, "tx3-lsp", "new");
+        tx.replace(&staged, &added).unwrap();
+
+        // the process dies while appending the next entry
+        let mut journal = std::fs::OpenOptions::new()
+            .append(true)
+            .open(config.backup_dir().join(JOURNAL_FILE))
+            .unwrap();
+        write!(journal, "{{\"target\":\"/tmp/tru").unwrap();
+        drop(tx);
+
+        let tx = Transaction::begin(&config).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&replaced).unwrap(), "old");
+        assert!(!added.exists());
+        assert!(!config.backup_dir().join(JOURNAL_FILE).exists());
+
+        tx.commit().unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+/// Program interpreter (dynamic loader) requested by an ELF executable, if
+/// it's dynamically linked
+fn elf_interpreter(elf: &[u8]) -> Option<String> {
+    const PT_INTERP: u32 = 3;
+
+    if elf.get(..4)? != b"\x7fELF" {
+        return None;
+    }
+
+    let is_64 = *elf.get(4)? == 2;
+    let is_le = *elf.get(5)? == 1;
+
+    let read = |offset: usize, size: usize| -> Option<u64> {
+        let bytes = elf.get(offset..offset.checked_add(size)?)?;
+        let mut value = 0u64;
+
+        for i in 0..size {
+            let byte = if is_le { bytes[size - 1 - i] } else { bytes[i] };
+            value = (value << 8) | byte as u64;
+        }
+
+        Some(value)
+    };
+
+    // field offsets of the ELF header and program headers, 64 vs 32 bits
+    let (phoff, phentsize, phnum) = if is_64 {
+        (read(0x20, 8)?, read(0x36, 2)?, read(0x38, 2)?)
+    } else {
+        (read(0x1c, 4)?, read(0x2a, 2)?, read(0x2c, 2)?)
+    };
+
+    for i in 0..phnum {
+        // the header comes from the file, offsets may point anywhere
+        let header = i
+            .checked_mul(phentsize)
+            .and_then(|offset| offset.checked_add(phoff))
+            .and_then(|offset| usize::try_from(offset).ok())?;
+
+        if read(header, 4)? as u32 != PT_INTERP {
+            continue;
+        }
+
+        let (offset, size) = if is_64 {
+            (
+                read(header.checked_add(0x08)?, 8)?,
+                read(header.checked_add(0x20)?, 8)?,
+            )
+        } else {
+            (
+                read(header.checked_add(0x04)?, 4)?,
+                read(header.checked_add(0x10)?, 4)?,
+            )
+        };
+
+        let start = usize::try_from(offset).ok()?;
+        let end = start.checked_add(usize::try_from(size).ok()?)?;
+        let interp = elf.get(start..end)?;
+
+        return Some(
+            String::from_utf8_lossy(interp)
+                .trim_end_matches('\0')
+                .to_string(),
+        );
+    }
+
+    None
+}
+
+/// Whether the machine runs musl rather than glibc, e.g. Alpine
+fn is_musl() -> bool {
+    if cfg!(target_env = "musl") {
+        return true;
+    }
+
+    // the loader of the system shell tells what the system is built on;
+    // the mere presence of a musl loader doesn't, glibc hosts can have one
+    if let Ok(sh) = std::fs::read("/bin/sh")
+        && let Some(interp) = elf_interpreter(&sh)
+    {
+        return interp.contains("ld-musl");
+    }
+
+    // a static shell says nothing, musl's ldd names itself instead
+    std::process::Command::new("ldd")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout).contains("musl")
+                || String::from_utf8_lossy(&output.stderr).contains("musl")
+        })
+}
+
+/// Target triple of the running machine, as used in release asset names
+pub fn detect() -> Option<String> {
+    // Map Rust's arch to our convention
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "x86_64",
+        "aarch64" => "aarch64",
+        _ => return None,
+    };
+
+    // Map Rust's OS to our convention
+    let os = match std::env::consts::OS {
+        "macos" => "apple-darwin",
+        "linux" if is_musl() => "unknown-linux-musl",
+        "linux" => "unknown-linux-gnu",
+        "windows" => "pc-windows-msvc",
+        _ => return None,
+    };
+
+    Some(format!("{arch}-{os}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(elf: &mut [u8], offset: usize, size: usize, value: u64, is_le: bool) {
+        let bytes = value.to_be_bytes();
+        let bytes = &bytes[8 - size..];
+
+        for (i, byte) in bytes.iter().enumerate() {
+            let at = if is_le {
+                offset + size - 1 - i
+            } else {
+                offset + i
+            };
+            elf[at] = *byte;
+        }
+    }
+
+    /// A minimal executable with a `PT_LOAD` program header, followed by a
+    /// `PT_INTERP` one when `interp` is given
+    fn elf(is_64: bool, is_le: bool, interp: Option<&str>) -> Vec<u8> {
+        let (ehsize, phentsize) = if is_64 { (64, 56) } else { (52, 32) };
+        let phnum = if interp.is_some() { 2 } else { 1 };
+        let interp_offset = ehsize + phnum * phentsize;
+
+        let mut elf = vec![0u8; interp_offset];
+        elf[..4].copy_from_slice(b"\x7fELF");
+        elf[4] = if is_64 { 2 } else { 1 };
+        elf[5] = if is_le { 1 } else { 2 };
+
+        if is_64 {
+            put(&mut elf, 0x20, 8, ehsize as u64, is_le);
+            put(&mut elf, 0x36, 2, phentsize as u64, is_le);
+            put(&mut elf, 0x38, 2, phnum as u64, is_le);
+        } else {
+            put(&mut elf, 0x1c, 4, ehsize as u64, is_le);
+            put(&mut elf, 0x2a, 2, phentsize as u64, is_le);
+            put(&mut elf, 0x2c, 2, phnum as u64, is_le);
+        }
+
+        put(&mut elf, ehsize, 4, 1, is_le);
+
+        if let Some(interp) = interp {
+            let header = ehsize + phentsize;
+            let size = interp.len() as u64 + 1;
+
+            put(&mut elf, header, 4, 3, is_le);
+
+            if is_64 {
+                put(&mut elf, header + 0x08, 8, interp_offset as u64, is_le);
+                put(&mut elf, header + 0x20, 8, size, is_le);
+            } else {
+                put(&mut elf, header + 0x04, 4, interp_offset as u64, is_le);
+                put(&mut elf, header + 0x10, 4, size, is_le);
+            }
+
+            elf.extend_from_slice(interp.as_bytes());
+            elf.push(0);
+        }
+
+        elf
+    }
+
+    #[test]
+    fn reads_the_interpreter_of_every_layout() {
+        for (is_64, is_le, interp) in [
+            (true, true, "/lib/ld-musl-x86_64.so.1"),
+            (true, false, "/lib64/ld64.so.1"),
+            (false, true, "/lib/ld-linux.so.2"),
+            (false, false, "/lib/ld-musl-mips.so.1"),
+        ] {
+            assert_eq!(
+                elf_interpreter(&elf(is_64, is_le, Some(interp))).as_deref(),
+                Some(interp),
+                "64 bit: {is_64}, little endian: {is_le}"
+            );
+        }
+    }
+
+    #[test]
+    fn static_binaries_have_no_interpreter() {
+        assert_eq!(elf_interpreter(&elf(true, true, None)), None);
+        assert_eq!(elf_interpreter(&elf(false, false, None)), None);
+    }
+
+    #[test]
+    fn malformed_headers_have_no_interpreter() {
+        assert_eq!(elf_interpreter(b"#!/bin/sh\n"), None);
+        assert_eq!(elf_interpreter(b"\x7fELF"), None);
+
+        let valid = elf(true, true, Some("/lib/ld-musl-x86_64.so.1"));
+
+        // program headers past the end of the address space
+        let mut elf = valid.clone();
+        put(&mut elf, 0x20, 8, u64::MAX, true);
+        assert_eq!(elf_interpreter(&elf), None);
+
+        // an interpreter running off the end of the file
+        let mut elf = valid.clone();
+        put(&mut elf, 64 + 56 + 0x20, 8, u64::MAX, true);
+        assert_eq!(elf_interpreter(&elf), None);
+
+        // cut short in the middle of the program headers
+        assert_eq!(elf_interpreter(&valid[..100]), None);
+    }
+}
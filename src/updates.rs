@@ -1,6 +1,10 @@
-use std::time::{Duration, SystemTime};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 
 use anyhow::Context as _;
+use futures_util::{StreamExt as _, TryStreamExt as _, stream};
 use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use tokio::fs;
@@ -8,6 +12,7 @@ use tokio::fs;
 use crate::{
     Config, bin,
     manifest::{Manifest, Tool},
+    receipts::{self, Receipt},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,7 +38,103 @@ impl Update {
     }
 }
 
-async fn find_installed_version(tool: &Tool, config: &Config) -> anyhow::Result<Option<Version>> {
+/// A tool name, optionally followed by `@<version requirement>`
+#[derive(Debug, Clone)]
+pub struct ToolSpec {
+    pub name: String,
+    pub version: Option<VersionReq>,
+}
+
+impl std::str::FromStr for ToolSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, version)) = s.split_once('@') else {
+            return Ok(Self {
+                name: s.to_string(),
+                version: None,
+            });
+        };
+
+        // a bare version means exactly that version, not semver's caret default
+        let version = match Version::parse(version) {
+            Ok(exact) => VersionReq::parse(&format!("={exact}"))?,
+            Err(_) => VersionReq::parse(version).context("parsing version requirement")?,
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            version: Some(version),
+        })
+    }
+}
+
+/// Narrows down the tools of the manifest a command acts on
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ToolFilter {
+    /// Only act on these tools (default: every tool in the manifest), use
+    /// `<tool>@<version>` to install a version outside the manifest
+    /// requirement
+    pub tools: Vec<ToolSpec>,
+
+    /// Skip these tools
+    #[arg(long)]
+    pub exclude: Vec<String>,
+}
+
+impl ToolFilter {
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, tool: &Tool) -> bool {
+        let included = self.tools.is_empty() || self.tools.iter().any(|t| t.name == tool.name);
+        let excluded = self.exclude.contains(&tool.name);
+
+        included && !excluded
+    }
+
+    /// Fails if any of the named tools is not part of the manifest
+    pub fn validate(&self, manifest: &Manifest) -> anyhow::Result<()> {
+        let names = self.tools.iter().map(|t| &t.name);
+
+        for name in names.chain(self.exclude.iter()) {
+            if manifest.tool_by_name(name).is_none() {
+                anyhow::bail!("{name} is not part of the toolchain");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Version requirements explicitly requested for each tool
+    pub fn requested_versions(&self) -> HashMap<String, String> {
+        self.tools
+            .iter()
+            .filter_map(|t| Some((t.name.clone(), t.version.as_ref()?.to_string())))
+            .collect()
+    }
+}
+
+/// Version of an installed tool, as recorded in `receipts` when it was
+/// installed. Tools without a receipt (installed by older versions or by
+/// hand) are asked for their version instead.
+pub async fn find_installed_version(
+    tool: &Tool,
+    receipts: &HashMap<String, Receipt>,
+    config: &Config,
+) -> anyhow::Result<Option<Version>> {
+    // global tools are looked up on PATH by their version command
+    if !tool.is_global && !tool.bin_path(config).exists() {
+        return Ok(None);
+    }
+
+    let recorded = receipts.get(&tool.name).and_then(Receipt::version);
+
+    if recorded.is_some() {
+        return Ok(recorded);
+    }
+
     let current_version = bin::check_current_version(tool, config).await;
 
     match current_version {
@@ -46,14 +147,32 @@ async fn find_installed_version(tool: &Tool, config: &Config) -> anyhow::Result<
     }
 }
 
-async fn evaluate_update(tool: &Tool, config: &Config) -> anyhow::Result<Option<Update>> {
-    let current = find_installed_version(tool, config).await?;
+/// Version of the `tx3up` executable, which is what the manifest's `self`
+/// entry describes
+pub async fn self_version(tool: &Tool) -> Option<Version> {
+    let exe = bin::self_exe().ok()?;
+
+    bin::check_version(tool, exe).await.ok()
+}
+
+async fn evaluate_update(
+    tool: &Tool,
+    is_self: bool,
+    receipts: &HashMap<String, Receipt>,
+    config: &Config,
+) -> anyhow::Result<Option<Update>> {
+    let current = if is_self {
+        self_version(tool).await
+    } else {
+        find_installed_version(tool, receipts, config).await?
+    };
+
     let requested = VersionReq::parse(&tool.version)?;
 
-    if let Some(current) = &current {
-        if requested.matches(&current) {
-            return Ok(None);
-        }
+    if let Some(current) = &current
+        && requested.matches(current)
+    {
+        return Ok(None);
     }
 
     Ok(Some(Update {
@@ -92,13 +211,43 @@ pub async fn clear_updates(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn check_updates(manifest: &Manifest, config: &Config) -> anyhow::Result<Vec<Update>> {
-    let mut updates = vec![];
+/// Evaluates every tool of the manifest, running up to `jobs` version
+/// commands at a time
+pub async fn check_updates(
+    manifest: &Manifest,
+    filter: &ToolFilter,
+    jobs: usize,
+    config: &Config,
+) -> anyhow::Result<Vec<Update>> {
+    filter.validate(manifest)?;
 
-    for tool in manifest.tools() {
-        if let Some(update) = evaluate_update(tool, config).await? {
-            updates.push(update);
-        }
+    let self_name = manifest.self_tool().map(|tool| tool.name.as_str());
+
+    let tools = manifest.tools().filter(|tool| filter.matches(tool));
+    let receipts = &receipts::load_receipts(config)?;
+
+    // evaluations without a receipt spawn the tool's version command, run a
+    // few of them at a time while keeping the manifest order
+    let evaluated: Vec<_> = stream::iter(tools)
+        .filter_map(|tool| async move {
+            let is_self = Some(tool.name.as_str()) == self_name;
+
+            // a copy managed by a package manager is not ours to update
+            if is_self && !config.self_update() {
+                return None;
+            }
+
+            Some(evaluate_update(tool, is_self, receipts, config))
+        })
+        .buffered(jobs.max(1))
+        .try_collect()
+        .await?;
+
+    let updates: Vec<_> = evaluated.into_iter().flatten().collect();
+
+    // the cached file always describes the whole toolchain
+    if !filter.is_empty() {
+        return Ok(updates);
     }
 
     if updates.is_empty() {
@@ -136,13 +285,19 @@ fn updates_are_stale(timestamp: Option<SystemTime>) -> bool {
 
 pub async fn load_updates(
     manifest: &Manifest,
+    filter: &ToolFilter,
+    jobs: usize,
     config: &Config,
     force_check: bool,
 ) -> anyhow::Result<Vec<Update>> {
+    if !filter.is_empty() {
+        return check_updates(manifest, filter, jobs, config).await;
+    }
+
     let timestamp = check_updates_timestamp(config).await?;
 
     if force_check || updates_are_stale(timestamp) {
-        check_updates(manifest, config).await?;
+        check_updates(manifest, filter, jobs, config).await?;
     }
 
     let updates_file = config.updates_file();
@@ -159,3 +314,88 @@ pub async fn load_updates(
 
     Ok(updates)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(extra: serde_json::Value) -> Tool {
+        let mut json = serde_json::json!({
+            "name": "trix",
+            "description": "",
+            "repo_owner": "tx3-lang",
+            "repo_name": "trix",
+            "version": "^0.5",
+        });
+
+        json.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn channel_tools_without_a_binary_are_not_installed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(tmp.path());
+
+        let version =
+            find_installed_version(&tool(serde_json::json!({})), &HashMap::new(), &config)
+                .await
+                .unwrap();
+
+        assert_eq!(version, None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn global_tools_are_found_on_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Config::for_tests(tmp.path());
+
+        // `sh` is on PATH but not in the current dir
+        let tool = tool(serde_json::json!({
+            "name": "sh",
+            "is_global": true,
+            "version_args": ["-c", "echo sh 0.5.3"],
+        }));
+
+        let version = find_installed_version(&tool, &HashMap::new(), &config)
+            .await
+            .unwrap();
+
+        assert_eq!(version, Some(Version::parse("0.5.3").unwrap()));
+    }
+
+    #[test]
+    fn bare_versions_are_exact() {
+        for (spec, version, matches) in [
+            ("trix@0.11.2", "0.11.2", true),
+            ("trix@0.11.2", "0.11.3", false),
+            ("trix@^0.10", "0.10.4", true),
+            ("trix@^0.10", "0.11.0", false),
+            ("trix@=0.12.0-beta.1", "0.12.0-beta.1", true),
+        ] {
+            let parsed: ToolSpec = spec.parse().unwrap();
+            let requested = parsed.version.unwrap();
+
+            assert_eq!(parsed.name, "trix");
+            assert_eq!(
+                requested.matches(&Version::parse(version).unwrap()),
+                matches,
+                "{spec} vs {version}"
+            );
+        }
+    }
+
+    #[test]
+    fn specs_without_a_version_follow_the_manifest() {
+        let parsed: ToolSpec = "tx3-lsp".parse().unwrap();
+
+        assert_eq!(parsed.name, "tx3-lsp");
+        assert!(parsed.version.is_none());
+
+        assert!("trix@latest".parse::<ToolSpec>().is_err());
+    }
+}
@@ -0,0 +1,98 @@
+#!/bin/bash
+
+# Uninstall E2E Test for tx3up
+# This test verifies that tx3up can remove a tool, a channel and the whole root.
+
+set -euo pipefail
+
+# Colors for output
+RED='\033[0;31m'
+GREEN='\033[0;32m'
+YELLOW='\033[1;33m'
+NC='\033[0m' # No Color
+
+# Test configuration
+TEST_NAME="uninstall"
+TEMP_DIR=$(mktemp -d)
+export TX3_ROOT="$TEMP_DIR/tx3_test"
+export TX3_CHANNEL="${TX3_CHANNEL:-stable}"
+# toolchain releases don't sign their manifests yet
+export TX3_ALLOW_UNSIGNED="${TX3_ALLOW_UNSIGNED:-1}"
+
+echo -e "${YELLOW}Starting $TEST_NAME test...${NC}"
+echo "TX3_ROOT: $TX3_ROOT"
+echo "TX3_CHANNEL: $TX3_CHANNEL"
+echo "Temp directory: $TEMP_DIR"
+
+# Cleanup function
+cleanup() {
+    echo -e "${YELLOW}Cleaning up test artifacts...${NC}"
+    rm -rf "$TEMP_DIR"
+}
+trap cleanup EXIT
+
+# Function to check that a path no longer exists
+check_absent() {
+    local path="$1"
+    local description="$2"
+
+    if [[ ! -e "$path" && ! -L "$path" ]]; then
+        echo -e "${GREEN}✓ $description removed: $path${NC}"
+        return 0
+    else
+        echo -e "${RED}✗ $description still exists: $path${NC}"
+        return 1
+    fi
+}
+
+# Main test execution
+main() {
+    echo -e "${YELLOW}Step 1: Performing initial installation${NC}"
+
+    if ! ./target/release/tx3up; then
+        echo -e "${RED}✗ Initial tx3up installation failed${NC}"
+        exit 1
+    fi
+
+    bin_dir="$TX3_ROOT/$TX3_CHANNEL/bin"
+    first_tool=$(find "$bin_dir" -type f -perm -u+x 2>/dev/null | head -n 1)
+
+    if [[ -z "$first_tool" ]]; then
+        echo -e "${RED}✗ No executable binaries found in bin directory${NC}"
+        exit 1
+    fi
+
+    tool_name=$(basename "$first_tool")
+    echo -e "${GREEN}✓ Initial installation completed, using $tool_name${NC}"
+
+    echo -e "${YELLOW}Step 2: Dry-run of a full uninstall${NC}"
+
+    ./target/release/tx3up uninstall --all --dry-run
+
+    if [[ -d "$TX3_ROOT" ]]; then
+        echo -e "${GREEN}✓ Dry-run left the root directory untouched${NC}"
+    else
+        echo -e "${RED}✗ Dry-run deleted the root directory${NC}"
+        exit 1
+    fi
+
+    echo -e "${YELLOW}Step 3: Uninstalling a single tool${NC}"
+
+    ./target/release/tx3up uninstall "$tool_name"
+    check_absent "$bin_dir/$tool_name" "Tool binary ($tool_name)"
+
+    echo -e "${YELLOW}Step 4: Uninstalling the channel${NC}"
+
+    ./target/release/tx3up uninstall --whole-channel
+    check_absent "$TX3_ROOT/$TX3_CHANNEL" "Channel directory ($TX3_CHANNEL)"
+
+    echo -e "${YELLOW}Step 5: Uninstalling everything${NC}"
+
+    ./target/release/tx3up uninstall --all
+    check_absent "$TX3_ROOT" "TX3 root directory"
+
+    echo -e "${GREEN}🎉 Uninstall test completed successfully!${NC}"
+}
+
+# Run the main test
+main "$@"
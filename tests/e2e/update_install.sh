@@ -16,6 +16,8 @@ TEST_NAME="update_install"
 TEMP_DIR=$(mktemp -d)
 export TX3_ROOT="$TEMP_DIR/tx3_test"
 export TX3_CHANNEL="${TX3_CHANNEL:-stable}"
+# toolchain releases don't sign their manifests yet
+export TX3_ALLOW_UNSIGNED="${TX3_ALLOW_UNSIGNED:-1}"
 
 echo -e "${YELLOW}Starting $TEST_NAME test...${NC}"
 echo "TX3_ROOT: $TX3_ROOT"